@@ -1,28 +1,88 @@
-use crate::utils;
+use crate::utils::{self, ListenAddr};
 use anyhow::Result;
-use std::path::Path;
+use std::fmt;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::UnixStream;
-pub async fn send<P: AsRef<Path>>(socket_path: Option<P>, command: &str) -> Result<()> {
-    let path = socket_path.map_or_else(
-        || utils::get_socket_path(None).into(),
-        |p| p.as_ref().to_path_buf(),
-    );
-    let mut stream = UnixStream::connect(path).await?;
+
+/// Code of failures whose reply carries none.
+const GENERIC_CODE: &str = "ERROR";
+
+/// A command the daemon answered with `fail:<CODE>: <message>`.
+#[derive(Debug)]
+pub struct CommandFailed {
+    /// Machine-readable code, eg. `WRONG_LAYOUT`.
+    pub code: String,
+    /// Human-readable message, followed by the details of the reply body if any.
+    pub message: String,
+}
+
+impl fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+impl CommandFailed {
+    /// Parse what follows `fail:` on a status line, with the reply `body` so far.
+    fn parse(status: &str, body: &str) -> Self {
+        let (code, msg) = status
+            .split_once(':')
+            .filter(|(code, _)| {
+                !code.is_empty() && code.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+            })
+            .unwrap_or((GENERIC_CODE, status));
+        Self {
+            code: code.to_owned(),
+            message: format!("{}\n{body}", msg.trim()).trim_end().to_owned(),
+        }
+    }
+}
+
+/// Send `command` to the daemon and return the body of its reply.
+///
+/// A reply is any number of `data: <line>` lines making up the body, terminated
+/// by a `success` or `fail:<CODE>: <message>` line. On failure the returned error
+/// is a `CommandFailed` whose message has the body, the details of the error,
+/// appended.
+///
+/// `socket_path` is a socket file, or an address the daemon was given with
+/// `--listen` (`unix:/path`, `abstract:@name` or `tcp:HOST:PORT`). Without one the
+/// socket of `profile` (see `--profile`) is used.
+pub async fn send(
+    socket_path: Option<String>,
+    profile: Option<&str>,
+    command: &str,
+) -> Result<String> {
+    let addr = match socket_path {
+        Some(s) => s.parse()?,
+        None => ListenAddr::Unix(utils::discover_socket_path(profile)?),
+    };
+    let mut stream = addr.connect().await?;
     stream.write_all(command.as_bytes()).await?;
     stream.write_all(b"\n").await?; // ensure newline, in case daemon cares
-    // Read the reply line
-    let (read_half, _) = stream.into_split();
+    // Read reply lines, collecting any data, until the final status line
+    let (read_half, _write_half) = tokio::io::split(stream);
     let mut reader = BufReader::new(read_half);
-    let mut resp = String::new();
-    reader.read_line(&mut resp).await?;
-    let resp = resp.trim_end();
-    match resp {
-        "success" => Ok(()),
-        s if s.starts_with("fail:") => {
-            let msg = s.strip_prefix("fail:").unwrap().trim();
-            anyhow::bail!("{msg}");
+    let mut body = String::new();
+    loop {
+        let mut resp = String::new();
+        if reader.read_line(&mut resp).await? == 0 {
+            anyhow::bail!("connection closed before a response was received");
+        }
+        let resp = resp.trim_end_matches('\n');
+        match resp {
+            "success" => return Ok(body),
+            s if s.starts_with("data:") => {
+                let line = s.strip_prefix("data:").unwrap();
+                body.push_str(line.strip_prefix(' ').unwrap_or(line));
+                body.push('\n');
+            }
+            s if s.starts_with("fail:") => {
+                let status = s.strip_prefix("fail:").unwrap();
+                return Err(CommandFailed::parse(status, &body).into());
+            }
+            _ => anyhow::bail!("unexpected response: {resp}"),
         }
-        _ => anyhow::bail!("unexpected response: {resp}"),
     }
 }
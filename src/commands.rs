@@ -1,16 +1,84 @@
-use crate::layout::{STACK_MAIN_DEFAULT_SIZE, StackLayout, WorkspaceLayout};
-#[cfg(feature = "wallpaper")]
+use crate::layout::{
+    Direction, MainPosition, RELAYOUT_STEP_DELAY_MS, SPIRAL_HYSTERESIS, SPIRAL_THROTTLE_MS,
+    STACK_MAIN_DEFAULT_SIZE, STACK_RESIZE_STEP, SplitDir, StackInsert, StackLayout,
+    WorkspaceLayout, hysteresis_in_range, parse_app_layout, parse_layout, parse_output_layout,
+    parse_preset, parse_workspace_layout_rule, ratio_in_range, size_in_range,
+};
+use crate::server::{DEFAULT_RENAMING_FORMAT, FOCUS_FADE_MS, RENAMING_DELAY_MS};
+use crate::utils::{ListenAddr, WorkspaceKey};
+use regex::Regex;
 use std::path::PathBuf;
 
+fn opacity_in_range(s: &str) -> Result<f32, String> {
+    let value: f32 = s
+        .parse()
+        .map_err(|_| format!("{s} is not a valid number"))?;
+    if (0.0..=1.0).contains(&value) {
+        return Ok(value);
+    }
+    Err(format!("{value} not in range 0-1"))
+}
+
+fn fade_steps_in_range(s: &str) -> Result<u32, String> {
+    let value: u32 = s
+        .parse()
+        .map_err(|_| format!("{s} is not a valid number"))?;
+    if (1..=20).contains(&value) {
+        return Ok(value);
+    }
+    Err(format!("{value} not in range 1-20"))
+}
+
+/// Parse an `APP_ID=COMMAND` pair such as `mpv=inhibit_idle focus`.
+fn parse_on_focus_app(s: &str) -> Result<(String, String), String> {
+    let (app, cmd) = s
+        .split_once('=')
+        .filter(|(app, cmd)| !app.is_empty() && !cmd.is_empty())
+        .ok_or_else(|| format!("{s} is not of the form APP_ID=COMMAND"))?;
+    Ok((app.to_owned(), cmd.to_owned()))
+}
+
+/// Parse an `APP_ID=OPACITY` pair such as `foot=0.95`.
+fn parse_app_opacity(s: &str) -> Result<(String, f32), String> {
+    let (app, opacity) = s
+        .split_once('=')
+        .ok_or_else(|| format!("{s} is not of the form APP_ID=OPACITY"))?;
+    Ok((app.to_owned(), opacity_in_range(opacity)?))
+}
+
 #[derive(clap::Parser, Debug)]
 pub struct DaemonArgs {
     /// Which layout should be the default when no other layout has been specified for
-    /// a workspace. Options are: manual, spiral and `stack_main`.
+    /// a workspace. Options are: manual, spiral, dwindle, `stack_main` and `centered_main`.
     #[arg(long, short = 'd', default_value = "manual")]
     pub default_layout: WorkspaceLayout,
 
-    /// This controls the default size of the main area in the `stack_main` layout.
-    #[arg(long, short = 's', default_value_t = STACK_MAIN_DEFAULT_SIZE)]
+    /// Default layout for workspaces on a given output, overriding `--default-layout`
+    /// there, eg. `eDP-1=stack_main`. Can be given once per output.
+    #[arg(long, value_name = "OUTPUT=LAYOUT", value_parser = parse_output_layout)]
+    pub output_layout: Vec<(String, WorkspaceLayout)>,
+
+    /// Default layout for workspaces whose name matches a regex, overriding
+    /// `--output-layout` and `--default-layout`, eg. `^chat=spiral`. Can be given
+    /// multiple times, the first matching rule wins.
+    #[arg(long, value_name = "REGEX=LAYOUT", value_parser = parse_workspace_layout_rule)]
+    pub workspace_layout_rule: Vec<(Regex, WorkspaceLayout)>,
+
+    /// Switch a workspace to a layout when a window of this `app_id` (or X11 class)
+    /// opens on it, eg. `gimp=manual`. A trailing `*` matches by prefix. Never
+    /// overrides a layout set with a command. Can be given multiple times, the
+    /// first matching rule wins.
+    #[arg(long, value_name = "APP_ID=LAYOUT", value_parser = parse_app_layout)]
+    pub app_layout: Vec<(String, WorkspaceLayout)>,
+
+    /// Named layout for `apply-preset`, written like the arguments of `change-layout`,
+    /// eg. `coding=stack-main --size 65 --stack-layout tabbed`. Can be given once per name.
+    #[arg(long, value_name = "NAME=LAYOUT", value_parser = parse_preset)]
+    pub preset: Vec<(String, WorkspaceLayout)>,
+
+    /// This controls the default size of the main area in the `stack_main` layout,
+    /// in percent (10-90).
+    #[arg(long, short = 's', value_parser = size_in_range, default_value_t = STACK_MAIN_DEFAULT_SIZE)]
     pub stack_main_default_size: u8,
 
     /// This controls the default sway layout of the stack area in the `stack_main` layout.
@@ -18,11 +86,116 @@ pub struct DaemonArgs {
     #[arg(long, short = 'k', default_value_t = StackLayout::Stacked)]
     pub stack_main_default_stack_layout: StackLayout,
 
+    /// This controls where the main area sits in the `stack_main` layout: right, with the
+    /// stack on the left, or bottom, with the stack as a strip along the top.
+    #[arg(long, default_value_t = MainPosition::Right)]
+    pub stack_main_default_main_position: MainPosition,
+
+    /// Which end of the `stack_main` stack a window pushed out of the main area by a new
+    /// window goes to: top or bottom. Without it, it goes next to the last focused
+    /// stack window.
+    #[arg(long, value_name = "top|bottom")]
+    pub stack_insert: Option<StackInsert>,
+
+    /// Never make the main area of the `stack_main` layout narrower (or lower, with the
+    /// main area at the bottom) than this many pixels when windows open or close,
+    /// raising its size in percent on small workspaces.
+    #[arg(long, value_name = "PX")]
+    pub stack_main_min_main_px: Option<u32>,
+
+    /// Size the main area and the stack of the `stack_main` layout in pixels of the
+    /// space between sway's inner gaps, measured from the tree. Sway's own percentages
+    /// count the gaps in, which leaves the main area a little off on gapped setups.
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    pub gap_aware_resize: bool,
+
+    /// Leave X11 dialogs and splash screens (windows with a parent or of type dialog or
+    /// splash) out of the layouts and workspace names, so they don't cause a relayout
+    /// or a rename each time one pops up.
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    pub ignore_transient: bool,
+
+    /// Share of the space the older window keeps when the `spiral` layout splits it,
+    /// eg. 0.62 for a golden ratio. Windows are split in equal halves when not set.
+    #[arg(long, value_name = "RATIO", value_parser = ratio_in_range)]
+    pub spiral_ratio: Option<f32>,
+
+    /// How much longer one side of a window has to be than the other before the `spiral`
+    /// layout changes its split, eg. 1.1 splits vertically only once the height exceeds
+    /// the width by 10%. Nearly square windows keep their split instead of flipping.
+    #[arg(long, value_name = "FACTOR", value_parser = hysteresis_in_range, default_value_t = SPIRAL_HYSTERESIS)]
+    pub spiral_hysteresis: f32,
+
+    /// Focus events that arrive within this many milliseconds of the last `spiral`
+    /// layout pass are ignored, raise it on slow (eg. remote) sessions.
+    #[arg(long, value_name = "MS", default_value_t = SPIRAL_THROTTLE_MS)]
+    pub spiral_throttle_ms: u64,
+
+    /// Leave a window unsplit by the `spiral` layout for this many milliseconds after its
+    /// size was seen changing, so a resize by hand isn't undone on the next focus.
+    #[arg(long, value_name = "MS")]
+    pub spiral_resize_cooldown_ms: Option<u64>,
+
+    /// Pause after each window a relayout (eg. `change-layout`) moves back, giving sway
+    /// time to settle. Lower it on fast setups, raise it if relayouts misplace windows.
+    #[arg(long, value_name = "MS", default_value_t = RELAYOUT_STEP_DELAY_MS)]
+    pub relayout_step_delay_ms: u64,
+
+    /// Every this many seconds, lay out again the `stack_main` workspaces that lost their
+    /// shape, eg. after windows were moved by hand. Skipped while window events keep
+    /// coming in, or while the layouts are paused.
+    #[arg(long, value_name = "SECS")]
+    pub reconcile_interval_secs: Option<u64>,
+
+    /// Pause the layouts while sway is in this mode, eg. `resize`, so windows resized
+    /// by hand aren't laid out again. Mode changes are seen when a key binding runs
+    /// `mode <name>`. Can be given multiple times.
+    #[arg(long, value_name = "MODE")]
+    pub pause_in_mode: Vec<String>,
+
     /// Enable automatic workspace renaming based on what is running
     /// in the workspace (eg. application name).
     #[arg(long, short = 'w')]
     pub workspace_renaming: bool,
 
+    /// JSON file mapping `app_id`/class to the icon used in renamed workspaces, eg.
+    /// `{"firefox": "", "org.gnome.*": ""}`. A trailing `*` matches by prefix.
+    /// Send SIGHUP to the daemon to reload it.
+    #[arg(long, value_name = "PATH")]
+    pub workspace_renaming_icons: Option<PathBuf>,
+
+    /// JSON file of `[REGEX, GLYPH]` pairs matched against window titles before the
+    /// icons file, eg. `[["^n?vim ", ""], ["htop", ""]]`. The first match wins.
+    /// Send SIGHUP to the daemon to reload it.
+    #[arg(long, value_name = "PATH")]
+    pub workspace_renaming_title_rules: Option<PathBuf>,
+
+    /// Icon used in renamed workspaces for apps missing from the icons file.
+    /// Without it the app name is used.
+    #[arg(long, value_name = "GLYPH")]
+    pub workspace_renaming_default_icon: Option<String>,
+
+    /// Format of renamed workspaces. Placeholders: `{num}` the workspace number,
+    /// `{name}` the focused app, `{icons}` every app on the workspace and `{count}`
+    /// the number of windows. Eg. `{num} {icons} [{count}]`.
+    #[arg(long, value_name = "FORMAT", default_value = DEFAULT_RENAMING_FORMAT)]
+    pub workspace_renaming_format: String,
+
+    /// Collapse consecutive identical icons in `{icons}` into one, so three terminals
+    /// show a single terminal icon.
+    #[arg(long)]
+    pub workspace_renaming_dedup: bool,
+
+    /// When collapsing identical icons, add how many there were, eg. `×3`.
+    #[arg(long, requires = "workspace_renaming_dedup")]
+    pub workspace_renaming_dedup_count: bool,
+
+    /// Wait this many milliseconds after a window event before renaming, a newer event
+    /// restarts the wait. Lower it if names lag behind, raise it to skip more
+    /// intermediate states.
+    #[arg(long, value_name = "MS", default_value_t = RENAMING_DELAY_MS)]
+    pub workspace_renaming_delay_ms: u64,
+
     /// Called when window comes into focus. To automatically set the opacity of
     /// all other windows to 0.8 for example, you would set this to:
     ///
@@ -46,6 +219,48 @@ pub struct DaemonArgs {
     #[arg(long, short = 'l')]
     pub on_window_focus_leave: Option<String>,
 
+    /// Windows matching this `app_id` (or X11 class) are skipped by `--on-window-focus`
+    /// and `--on-window-focus-leave`, eg. fullscreen video players. Can be given multiple
+    /// times. A trailing `*` matches by prefix, eg. `mpv*`.
+    #[arg(long, value_name = "APP_ID")]
+    pub focus_hook_exclude: Vec<String>,
+
+    /// Called when a window of this `app_id` (or X11 class) comes into focus, after
+    /// `--on-window-focus`, eg. `spotify=opacity 1`. A trailing `*` matches by prefix.
+    /// Can be given multiple times, every matching rule runs.
+    #[arg(long, value_name = "APP_ID=COMMAND", value_parser = parse_on_focus_app)]
+    pub on_focus_app: Vec<(String, String)>,
+
+    /// Dim windows by how recently they were focused: the focused window gets
+    /// opacity 1, the one before it 1 - STEP and so on, down to MIN. Eg. `0.6 0.1`.
+    #[arg(long, num_args = 2, value_names = ["MIN", "STEP"], value_parser = opacity_in_range)]
+    pub opacity_ramp: Option<Vec<f32>>,
+
+    /// Fixed opacity of the windows of an `app_id` (or X11 class), eg. `foot=0.95`. It is
+    /// the base opacity of these windows: `--opacity-ramp` multiplies it, and it overrides
+    /// opacities set by `--on-window-focus`/`--on-window-focus-leave`. A trailing `*`
+    /// matches by prefix. Can be given multiple times, the first matching rule wins.
+    #[arg(long, value_name = "APP_ID=OPACITY", value_parser = parse_app_opacity)]
+    pub app_opacity: Vec<(String, f32)>,
+
+    /// Border width in pixels of the focused window, set with `border pixel`.
+    #[arg(long, value_name = "PX")]
+    pub focused_border: Option<u32>,
+
+    /// Border width in pixels of windows once they lose focus, set with `border pixel`.
+    #[arg(long, value_name = "PX")]
+    pub unfocused_border: Option<u32>,
+
+    /// Fade the focused window in with this many `opacity` commands (1-20), from the
+    /// opacity it had unfocused (`--on-window-focus-leave` or `--opacity-ramp`) to its
+    /// focused one. Off by default, every step is a command sent to sway.
+    #[arg(long, value_name = "N", value_parser = fade_steps_in_range)]
+    pub focus_fade_steps: Option<u32>,
+
+    /// How long the fade of `--focus-fade-steps` takes, in milliseconds.
+    #[arg(long, value_name = "MS", default_value_t = FOCUS_FADE_MS)]
+    pub focus_fade_ms: u64,
+
     /// Called when persway exits. This can be used to reset any opacity changes
     /// or other settings when persway exits. For example, if changing the opacity
     /// on window focus, you would probably want to reset that on exit like this:
@@ -55,28 +270,278 @@ pub struct DaemonArgs {
     /// Eg. set all tiling windows to opacity 1
     #[arg(long, short = 'e')]
     pub on_exit: Option<String>,
+
+    /// Called when the last window of a workspace closes or moves away, eg.
+    /// `workspace back_and_forth` to leave workspaces once they are empty.
+    #[arg(long, value_name = "COMMAND")]
+    pub on_workspace_empty: Option<String>,
+
+    /// Windows matching this `app_id` (or X11 class) are never auto-tiled and are left
+    /// where sway puts them, just like floating windows. Can be given multiple times.
+    /// A trailing `*` matches by prefix, eg. `org.mozilla.*`.
+    #[arg(long, value_name = "APP_ID")]
+    pub layout_ignore: Vec<String>,
+
+    /// Only lay out and rename these workspaces, by number or name, eg. `1,2,web`.
+    /// All workspaces are managed when not given.
+    #[arg(long, value_name = "WORKSPACES", value_delimiter = ',')]
+    pub managed_workspaces: Vec<WorkspaceKey>,
+
+    /// Never lay out or rename these workspaces, by number or name, eg. `9,games`.
+    #[arg(long, value_name = "WORKSPACES", value_delimiter = ',')]
+    pub unmanaged_workspaces: Vec<WorkspaceKey>,
+
+    /// Still run `--on-window-focus` and the other focus hooks on workspaces that
+    /// aren't managed.
+    #[arg(long)]
+    pub unmanaged_focus_hooks: bool,
+
+    /// Inner gaps in pixels for workspaces with more than one tiled window. Workspaces
+    /// with a single tiled window get no gaps, like sway's `smart_gaps` but per event.
+    #[arg(long, value_name = "PX")]
+    pub smart_gaps: Option<u32>,
+
+    /// Listen for commands on this address instead of the socket file of `--socket-path`:
+    /// `unix:/path`, `abstract:@name` (Linux) or `tcp:127.0.0.1:PORT`. Anyone who can
+    /// reach a TCP address can control persway, keep it on the loopback interface.
+    #[arg(long, value_name = "ADDR")]
+    pub listen: Option<ListenAddr>,
+
+    /// Close control socket connections that send no command for this many seconds, so
+    /// dead clients keeping a connection open don't pile up. Connections stay open for
+    /// as long as their client likes when not set.
+    #[arg(long, value_name = "SECS")]
+    pub client_idle_timeout_secs: Option<u64>,
+
+    /// Log the sway commands persway would run (at info level) instead of running them.
+    /// Handy for debugging layouts and filing bug reports.
+    #[arg(long)]
+    pub dry_run: bool,
 }
 
 #[derive(clap::Parser, Debug)]
 pub enum PerswayCommand {
     /// Starts the persway daemon
-    Daemon(DaemonArgs),
+    Daemon(Box<DaemonArgs>),
     /// Applies to stack main layout - focuses the next stacked window
     StackFocusNext,
     /// Applies to stack main layout - focuses the previous stacked window
     StackFocusPrev,
+    /// Applies to stack main layout - shows the next window of a tabbed or stacked stack, keeping the focus on the main window
+    StackPageNext,
+    /// Applies to stack main layout - shows the previous window of a tabbed or stacked stack, keeping the focus on the main window
+    StackPagePrev,
     /// Applies to stack main layout - swaps the current stacked window with the main window
     StackSwapMain,
+    /// Applies to stack main layout - swaps the most recently focused stacked window with the main window, even when the focus is on main
+    StackSwapLastFocused,
+    /// Applies to stack main layout - moves the focused window within the stack, or between main and the stack, keeping the main/stack shape
+    StackMove {
+        /// up, down, left or right: along the stack it reorders, towards or away from main it swaps with main
+        #[arg(long)]
+        dir: Direction,
+    },
     /// Applies to stack main layout - pops the top of the stack into main while pushing the old main window to the bottom of the stack
     StackMainRotateNext,
     /// Applies to stack main layout - pops the top of the bottom of the stack into main while pushing the old main window to the top of the stack
     StackMainRotatePrev,
-    /// Changes the layout of the focused workspace
+    /// Applies to stack main layout - sets the size of the main area
+    StackMainSetSize {
+        /// Size of the main area in percent (10-90)
+        #[arg(long, short = 's', value_parser = size_in_range)]
+        size: u8,
+    },
+    /// Applies to stack main layout - brings the focused window into main and grows main to 90%, run it again to restore the size
+    StackZoomToggle,
+    /// Applies to stack main layout - toggles the stack area between tabbed and stacked without moving any windows
+    StackLayoutToggle,
+    /// Applies to stack main layout - sets the layout of the stack area and of the windows added to it later, without moving any windows
+    StackSetLayout {
+        /// tabbed, stacked or tiled
+        stack_layout: StackLayout,
+    },
+    /// Applies to stack main layout - gives every window of a tiled stack the same size, tabbed and stacked stacks are left alone
+    StackBalance,
+    /// Applies to stack main layout - makes the focused window of a tiled stack taller (wider with main at the bottom), taking the space from its neighbors
+    StackGrow {
+        /// How much to grow by, in percent of the stack
+        #[arg(long, short = 'a', default_value_t = STACK_RESIZE_STEP)]
+        amount: u8,
+    },
+    /// Applies to stack main layout - makes the focused window of a tiled stack shorter (narrower with main at the bottom), giving the space to its neighbors
+    StackShrink {
+        /// How much to shrink by, in percent of the stack
+        #[arg(long, short = 'a', default_value_t = STACK_RESIZE_STEP)]
+        amount: u8,
+    },
+    /// Changes the layout of the focused workspace, or of the one given with `--workspace`
     ChangeLayout {
         /// Change the layout of the focused workspace, can be any of:
-        /// manual, spiral, `stack_main`
+        /// manual, spiral, dwindle, `stack_main`, `centered_main`
         #[command(subcommand)]
         layout: WorkspaceLayout,
+        /// Workspace to change instead of the focused one, by number or name
+        #[arg(long)]
+        workspace: Option<WorkspaceKey>,
+    },
+    /// Undoes the last stack-swap-main, stack-main-set-size or change-layout, if nothing else changed the workspace since
+    Undo,
+    /// Changes the layout of the focused workspace to a preset given to the daemon with `--preset`
+    ApplyPreset {
+        /// Name of the preset
+        name: String,
+        /// Workspace to change instead of the focused one, by number or name
+        #[arg(long)]
+        workspace: Option<WorkspaceKey>,
+    },
+    /// Switches the focused workspace to the next layout of a list
+    CycleLayout {
+        /// Layouts to cycle through, in order [default: spiral `stack_main` manual]
+        #[arg(value_parser = parse_layout)]
+        layouts: Vec<WorkspaceLayout>,
+        /// Workspace to change instead of the focused one, by number or name
+        #[arg(long)]
+        workspace: Option<WorkspaceKey>,
+    },
+    /// Re-applies the layout of the focused workspace from scratch
+    Relayout {
+        /// Workspace to lay out instead of the focused one, by number or name
+        #[arg(long)]
+        workspace: Option<WorkspaceKey>,
+    },
+    /// Freezes the focused workspace in manual layout, or restores the layout it had before
+    ToggleManual {
+        /// Workspace to change instead of the focused one, by number or name
+        #[arg(long)]
+        workspace: Option<WorkspaceKey>,
+    },
+    /// Focuses the previously focused window, on any workspace. Run it again to go back and forth
+    FocusBack,
+    /// Focuses the most recently focused window of an app, on any workspace, and fails if it has no window
+    FocusApp {
+        /// `app_id` (or X11 class) of the app, a trailing `*` matches by prefix
+        #[arg(long)]
+        app_id: String,
+    },
+    /// Applies to manual and spiral layouts - rotates the focused workspace 90 degrees by flipping every horizontal split to vertical and back
+    Transpose,
+    /// Applies to spiral layout - forces every split of the focused workspace to one direction, whatever the shape of the window
+    SpiralForceDir {
+        /// h (side by side), v (on top of each other) or auto (by aspect ratio, the default)
+        #[arg(long)]
+        dir: SplitDir,
+    },
+    /// Focuses the window nearest to the focused one in a direction, going by where windows are on screen rather than by the tree
+    FocusSpatial {
+        /// up, down, left or right
+        #[arg(long)]
+        dir: Direction,
+    },
+    /// Swaps the focused window with its neighbor in a direction, wrapping around at the edge
+    SwapDirection {
+        /// up, down, left or right
+        #[arg(long)]
+        dir: Direction,
+    },
+    /// Moves every tiled window of a workspace to another one and lays that one out again
+    MergeWorkspace {
+        /// Workspace to empty, by number or name
+        #[arg(long)]
+        from: WorkspaceKey,
+        /// Workspace to move the windows to, by number or name
+        #[arg(long)]
+        to: WorkspaceKey,
+    },
+    /// Exchanges all windows of two workspaces, each workspace keeps its own layout
+    SwapWorkspaces {
+        /// First workspace, by number or name
+        #[arg(long)]
+        a: WorkspaceKey,
+        /// Second workspace, by number or name
+        #[arg(long)]
+        b: WorkspaceKey,
+    },
+    /// Toggles the window with a mark in and out of view (via the scratchpad), marking the focused window if none has it yet
+    ScratchToggle {
+        /// Mark of the window, eg. `dropdown`
+        mark: String,
+    },
+    /// Floats the focused window centered and sticky, so it shows on every workspace. Run it again to tile the window again
+    PinToggle,
+    /// Moves the focused window out of the way to a new workspace, numbered past the highest one, and stays where you are
+    Stash,
+    /// Brings the most recently stashed window back to the focused workspace
+    Unstash,
+    /// Swaps the windows carrying two marks, wherever they are
+    SwapMarks {
+        /// Mark of the first window
+        #[arg(long)]
+        a: String,
+        /// Mark of the second window
+        #[arg(long)]
+        b: String,
+    },
+    /// Saves the layout and windows of the focused workspace as a snapshot, in `$XDG_STATE_HOME/persway`
+    SaveSnapshot {
+        /// Name of the snapshot
+        name: String,
+    },
+    /// Moves the windows of a snapshot back onto the focused workspace in its layout, skipping closed ones
+    RestoreSnapshot {
+        /// Name of the snapshot
+        name: String,
+    },
+    /// Removes the split containers of the focused workspace that hold a single window or container, left behind by moving windows around
+    Flatten,
+    /// Prints the id, app id, title and role (main, stack, tiled or floating) of the focused window as JSON, eg. for bar widgets
+    FocusedWindow,
+    /// Lists the containers and windows of the focused workspace in tree order, with the role of each window (main, stack, tiled or floating) and which container is the stack, eg. for bug reports
+    ListWindows,
+    /// Lists the `_stack_*` marks the stack layouts use, with the container carrying each
+    ListMarks,
+    /// Removes `_stack_*` marks that no longer name a stack, left over by a failed layout command
+    CleanMarks,
+    /// Drops the layout settings kept for workspaces that no longer exist, printing how many were removed
+    GcWorkspaces,
+    /// Sets focus commands for the focused workspace in place of `--on-window-focus` and `--on-window-focus-leave`. Without any, the workspace uses the global ones again
+    SetFocusHooks {
+        /// Run when a window of the workspace gains focus, nothing if empty
+        #[arg(long, num_args = 0.., value_name = "COMMAND")]
+        focus: Option<Vec<String>>,
+        /// Run when a window focused on the workspace loses focus, nothing if empty
+        #[arg(long, num_args = 0.., value_name = "COMMAND")]
+        leave: Option<Vec<String>>,
+    },
+    /// Stops laying out windows automatically until `resume`, without stopping the daemon. Commands still work
+    Pause {
+        /// Also stop workspace renaming and the focus hooks
+        #[arg(long)]
+        all: bool,
+    },
+    /// Starts laying out windows automatically again after `pause`
+    Resume,
+    /// Renames the focused workspace, keeping its number, and keeps workspace renaming from changing the name until `unlock-name`
+    LockName {
+        /// New name, after the number, eg. `chat` for `3: chat`
+        #[arg(required = true, num_args = 1..)]
+        name: Vec<String>,
+    },
+    /// Lets workspace renaming name the focused workspace again after `lock-name`
+    UnlockName,
+    /// Turns automatic workspace renaming on or off until the daemon restarts, printing the new state
+    ToggleRenaming,
+    /// Stops the daemon, running its `--on-exit` command first
+    Quit,
+    /// Prints the daemon's uptime, settings and the layout of every known workspace
+    Status,
+    /// Prints the settings the daemon runs with, after merging the config files and its command line, eg. to attach to a bug report
+    DumpConfig,
+    /// Checks that the daemon is running, without changing anything
+    Ping,
+    /// Changes the log level of the running daemon until it restarts, replacing `RUST_LOG`
+    SetLogLevel {
+        /// off, error, warn, info, debug or trace
+        level: log::LevelFilter,
     },
     #[cfg(feature = "wallpaper")]
     SetWallpaper {
@@ -88,3 +553,18 @@ pub enum PerswayCommand {
         output: Option<String>,
     },
 }
+
+impl PerswayCommand {
+    /// Workspace given with `--workspace` to run the command on instead of the
+    /// focused one, if any.
+    pub fn workspace(&self) -> Option<&WorkspaceKey> {
+        match self {
+            Self::ChangeLayout { workspace, .. }
+            | Self::ApplyPreset { workspace, .. }
+            | Self::CycleLayout { workspace, .. }
+            | Self::Relayout { workspace }
+            | Self::ToggleManual { workspace } => workspace.as_ref(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Daemon settings from config files, layered under the command line.
+//!
+//! Settings are read from, later ones winning key by key:
+//! 1. `/etc/persway/config.toml`, defaults shipped by packagers.
+//! 2. `$XDG_CONFIG_HOME/persway/config.toml` (`~/.config/persway/config.toml` if not set).
+//! 3. The command line.
+//!
+//! Keys are the long options of `persway daemon` without the leading `--`, eg.
+//! `default-layout = "spiral"`, `workspace-renaming = true` or `app-opacity = ["foot=0.95"]`.
+
+use anyhow::{Context, Result, bail};
+use clap::{ArgMatches, Command, parser::ValueSource};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Config file with the system-wide defaults.
+const SYSTEM_CONFIG: &str = "/etc/persway/config.toml";
+
+/// Where settings come from, for error messages.
+pub const PRECEDENCE: &str = "settings are read from /etc/persway/config.toml, then \
+     $XDG_CONFIG_HOME/persway/config.toml, then the command line, later ones win";
+
+/// The config file of the user, `None` if neither `XDG_CONFIG_HOME` nor `HOME` is set.
+fn user_config() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("persway/config.toml"))
+}
+
+/// The config files that exist, in the order they are read, for `persway dump-config`.
+pub fn files() -> Vec<PathBuf> {
+    [Some(PathBuf::from(SYSTEM_CONFIG)), user_config()]
+        .into_iter()
+        .flatten()
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Parse the config file at `path`, `None` if there is none.
+fn read(path: &Path) -> Result<Option<Table>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let table = contents
+        .parse::<Table>()
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    log::debug!("config: read {}", path.display());
+    Ok(Some(table))
+}
+
+/// A single value of a setting as an argument.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(n) => Some(n.to_string()),
+        Value::Float(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// The settings of the config files that weren't given on the command line, as
+/// arguments to append to it.
+///
+/// `daemon` is the `persway daemon` command and `cli` what was parsed of its
+/// command line.
+pub fn daemon_args(daemon: &Command, cli: &ArgMatches) -> Result<Vec<String>> {
+    let mut settings = BTreeMap::new();
+    for path in [Some(PathBuf::from(SYSTEM_CONFIG)), user_config()]
+        .into_iter()
+        .flatten()
+    {
+        let Some(table) = read(&path)? else {
+            continue;
+        };
+        for (key, value) in table {
+            if !daemon.get_arguments().any(|a| a.get_long() == Some(&key)) {
+                bail!(
+                    "unknown key '{key}' in {}, keys are the long options of `persway daemon` \
+                     without the leading '--', eg. default-layout ({PRECEDENCE})",
+                    path.display()
+                );
+            }
+            settings.insert(key, (value, path.clone()));
+        }
+    }
+
+    let mut args = Vec::new();
+    for (key, (value, path)) in settings {
+        let Some(arg) = daemon.get_arguments().find(|a| a.get_long() == Some(&key)) else {
+            continue;
+        };
+        if cli.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            log::debug!(
+                "config: {key} from {} overridden on the command line",
+                path.display()
+            );
+            continue;
+        }
+        let invalid = || {
+            anyhow::anyhow!(
+                "'{key}' in {} must be a string, number, boolean or array of those",
+                path.display()
+            )
+        };
+        let setting = match &value {
+            // Options like `gap-aware-resize` take the value, flags are only given
+            Value::Boolean(on) if arg.get_action().takes_values() => vec![format!("--{key}={on}")],
+            Value::Boolean(true) => vec![format!("--{key}")],
+            Value::Boolean(false) => continue,
+            Value::Array(values) => {
+                let values = values.iter().map(scalar).collect::<Option<Vec<_>>>();
+                let values = values.ok_or_else(invalid)?;
+                // Options like `opacity-ramp` take all values at once, others are repeated
+                if arg.get_num_args().is_some_and(|n| n.max_values() > 1) {
+                    std::iter::once(format!("--{key}")).chain(values).collect()
+                } else {
+                    values.into_iter().map(|v| format!("--{key}={v}")).collect()
+                }
+            }
+            value => vec![format!("--{key}={}", scalar(value).ok_or_else(invalid)?)],
+        };
+        // Checked on its own so a bad value is reported with the file it is in
+        if let Err(e) = daemon
+            .clone()
+            .no_binary_name(true)
+            .try_get_matches_from(&setting)
+        {
+            bail!("invalid '{key}' in {} ({PRECEDENCE}): {e}", path.display());
+        }
+        args.extend(setting);
+    }
+    log::debug!("config: adding {args:?}");
+    Ok(args)
+}
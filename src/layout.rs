@@ -1,4 +1,6 @@
 use anyhow::{Result, anyhow};
+use clap::Parser;
+use regex::Regex;
 use std::{
     fmt::{Display, Formatter},
     ops::RangeInclusive,
@@ -6,15 +8,26 @@ use std::{
 };
 
 pub const STACK_MAIN_DEFAULT_SIZE: u8 = 70;
+pub const CENTERED_MAIN_DEFAULT_SIZE: u8 = 50;
+pub const STACK_ZOOM_SIZE: u8 = 90;
+pub const STACK_RESIZE_STEP: u8 = 5;
+pub const SPIRAL_THROTTLE_MS: u64 = 50;
+pub const SPIRAL_HYSTERESIS: f32 = 1.1;
+pub const RELAYOUT_STEP_DELAY_MS: u64 = 50;
 
 impl FromStr for WorkspaceLayout {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self> {
         match s {
             "spiral" => Ok(Self::Spiral),
+            "dwindle" => Ok(Self::Dwindle),
             "stack_main" => Ok(Self::StackMain {
                 stack_layout: StackLayout::Stacked,
                 size: STACK_MAIN_DEFAULT_SIZE,
+                main_position: MainPosition::Right,
+            }),
+            "centered_main" => Ok(Self::CenteredMain {
+                size: CENTERED_MAIN_DEFAULT_SIZE,
             }),
             "manual" => Ok(Self::Manual),
             s => Err(anyhow!("I don't know about the layout '{s}'")),
@@ -26,28 +39,184 @@ impl Display for WorkspaceLayout {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let string_layout = match self {
             Self::Spiral => String::from("spiral"),
-            Self::StackMain { stack_layout, size } => {
-                format!("stack_main {stack_layout} {size}")
-            }
+            Self::Dwindle => String::from("dwindle"),
+            Self::StackMain {
+                stack_layout,
+                size,
+                main_position: MainPosition::Right,
+            } => format!("stack_main {stack_layout} {size}"),
+            Self::StackMain {
+                stack_layout,
+                size,
+                main_position,
+            } => format!("stack_main {stack_layout} {size} {main_position}"),
+            Self::CenteredMain { size } => format!("centered_main {size}"),
             Self::Manual => String::from("manual"),
         };
         write!(f, "{string_layout}")
     }
 }
 
-const SIZE_RANGE: RangeInclusive<usize> = 10..=90;
+pub const SIZE_RANGE: RangeInclusive<usize> = 10..=90;
+
+/// Parse an `OUTPUT=LAYOUT` pair such as `eDP-1=stack_main`, `-` may be used in
+/// layout names as well (`eDP-1=stack-main`).
+pub fn parse_output_layout(s: &str) -> Result<(String, WorkspaceLayout), String> {
+    parse_keyed_layout(s, "OUTPUT=LAYOUT")
+}
+
+/// Parse an `APP_ID=LAYOUT` pair such as `gimp=manual`.
+pub fn parse_app_layout(s: &str) -> Result<(String, WorkspaceLayout), String> {
+    parse_keyed_layout(s, "APP_ID=LAYOUT")
+}
+
+/// Parse a `REGEX=LAYOUT` workspace rule such as `^chat=spiral`, compiling the regex.
+/// The layout follows the last `=`, so the regex may contain one.
+pub fn parse_workspace_layout_rule(s: &str) -> Result<(Regex, WorkspaceLayout), String> {
+    let (regex, layout) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("{s} is not of the form REGEX=LAYOUT"))?;
+    let regex = Regex::new(regex).map_err(|e| format!("invalid workspace regex: {e}"))?;
+    Ok((regex, parse_layout(layout)?))
+}
+
+fn parse_keyed_layout(s: &str, form: &str) -> Result<(String, WorkspaceLayout), String> {
+    let (key, layout) = s
+        .split_once('=')
+        .ok_or_else(|| format!("{s} is not of the form {form}"))?;
+    Ok((key.to_owned(), parse_layout(layout)?))
+}
+
+/// Parse a `NAME=LAYOUT` preset, the layout written like the arguments of
+/// `change-layout`, eg. `coding=stack-main --size 65 --stack-layout tabbed`.
+pub fn parse_preset(s: &str) -> Result<(String, WorkspaceLayout), String> {
+    let (name, spec) = s
+        .split_once('=')
+        .ok_or_else(|| format!("{s} is not of the form NAME=LAYOUT"))?;
+    let layout = parse_layout_args(spec).map_err(|e| format!("preset {name}: {e}"))?;
+    Ok((name.to_owned(), layout))
+}
+
+/// Parse a layout written like the arguments of `change-layout`, eg.
+/// `stack-main --size 65`, the inverse of `WorkspaceLayout::to_args`.
+pub fn parse_layout_args(spec: &str) -> Result<WorkspaceLayout, String> {
+    let mut words = spec.split_whitespace().map(String::from);
+    let layout = words
+        .next()
+        .ok_or_else(|| String::from("no layout given"))?
+        .replace('_', "-");
+    WorkspaceLayout::try_parse_from(
+        std::iter::once(String::from("layout"))
+            .chain(std::iter::once(layout))
+            .chain(words),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Parse a layout name, accepting `-` as well as `_` (`stack-main`, `stack_main`).
+pub fn parse_layout(s: &str) -> Result<WorkspaceLayout, String> {
+    s.replace('-', "_")
+        .parse()
+        .map_err(|e: anyhow::Error| e.to_string())
+}
+
+const RATIO_RANGE: RangeInclusive<f32> = 0.1..=0.9;
+
+/// Parse a split ratio such as `0.62`, the share of the space the older window keeps.
+pub fn ratio_in_range(s: &str) -> Result<f32, String> {
+    let ratio: f32 = s.parse().map_err(|_| format!("{s} is not a valid ratio"))?;
+    if RATIO_RANGE.contains(&ratio) {
+        return Ok(ratio);
+    }
+    Err(format!(
+        "ratio not in range {}-{}",
+        RATIO_RANGE.start(),
+        RATIO_RANGE.end()
+    ))
+}
+
+const HYSTERESIS_RANGE: RangeInclusive<f32> = 1.0..=2.0;
+
+/// Parse how much longer one side of a window has to be than the other for spiral
+/// to change its split, eg. `1.1`.
+pub fn hysteresis_in_range(s: &str) -> Result<f32, String> {
+    let factor: f32 = s
+        .parse()
+        .map_err(|_| format!("{s} is not a valid factor"))?;
+    if HYSTERESIS_RANGE.contains(&factor) {
+        return Ok(factor);
+    }
+    Err(format!(
+        "factor not in range {}-{}",
+        HYSTERESIS_RANGE.start(),
+        HYSTERESIS_RANGE.end()
+    ))
+}
+
+/// The amount for `resize set` that gives a tiled container `percent` of `total`
+/// pixels, shared by `count` containers with `gap` pixels between each two.
+///
+/// Sway takes `ppt` of the whole `total`, gaps included, which makes containers
+/// a bit off with gaps. With a gap the amount is in pixels of the space left
+/// between the gaps instead.
+pub fn resize_amount(percent: u8, total: i32, count: i32, gap: Option<i32>) -> String {
+    match gap.filter(|&gap| gap > 0 && total > 0) {
+        Some(gap) => format!(
+            "{} px",
+            (total - gap * (count - 1)).max(0) * i32::from(percent) / 100
+        ),
+        None => format!("{percent} ppt"),
+    }
+}
 
-fn size_in_range(s: &str) -> Result<u8, String> {
+/// Parse the size of a main area in percent, restricted to `SIZE_RANGE`.
+pub fn size_in_range(s: &str) -> Result<u8, String> {
     let size: usize = s.parse().map_err(|_| format!("{s} is not a valid size"))?;
     if SIZE_RANGE.contains(&size) {
         // Map the TryFromIntError into a String so the '?' works
         return u8::try_from(size).map_err(|e| e.to_string());
     }
-    Err(format!(
-        "size not in range {}-{}",
+    Err(size_error(size))
+}
+
+fn size_error(size: usize) -> String {
+    format!(
+        "size {size} not in range {}-{} (percent of the workspace)",
         SIZE_RANGE.start(),
         SIZE_RANGE.end()
-    ))
+    )
+}
+
+impl WorkspaceLayout {
+    /// The layout written like the arguments of `change-layout`, with every parameter.
+    pub fn to_args(&self) -> String {
+        match self {
+            Self::Spiral => String::from("spiral"),
+            Self::Dwindle => String::from("dwindle"),
+            Self::StackMain {
+                size,
+                stack_layout,
+                main_position,
+            } => format!(
+                "stack-main --size {size} --stack-layout {stack_layout} --main-position {main_position}"
+            ),
+            Self::CenteredMain { size } => format!("centered-main --size {size}"),
+            Self::Manual => String::from("manual"),
+        }
+    }
+
+    /// Check the main area size of layouts that have one, for layouts that didn't
+    /// come through the CLI parser.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::StackMain { size, .. } | Self::CenteredMain { size }
+                if !SIZE_RANGE.contains(&usize::from(*size)) =>
+            {
+                Err(anyhow!(size_error(usize::from(*size))))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl FromStr for StackLayout {
@@ -80,10 +249,92 @@ pub enum StackLayout {
     Tiled,
 }
 
+impl FromStr for StackInsert {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "top" => Ok(Self::Top),
+            "bottom" => Ok(Self::Bottom),
+            s => Err(anyhow!(
+                "I don't know about the stack insert position '{s}'"
+            )),
+        }
+    }
+}
+
+impl Display for StackInsert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let position = match self {
+            Self::Top => "top",
+            Self::Bottom => "bottom",
+        };
+        write!(f, "{position}")
+    }
+}
+
+/// Which end of the `stack_main` stack the window pushed out of main goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackInsert {
+    /// The start of the stack, first in a tabbed or stacked bar
+    Top,
+    /// The end of the stack
+    Bottom,
+}
+
+impl FromStr for MainPosition {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "right" => Ok(Self::Right),
+            "bottom" => Ok(Self::Bottom),
+            s => Err(anyhow!("I don't know about the main position '{s}'")),
+        }
+    }
+}
+
+impl Display for MainPosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let position = match self {
+            Self::Right => "right",
+            Self::Bottom => "bottom",
+        };
+        write!(f, "{position}")
+    }
+}
+
+/// Where the main area of the `stack_main` layout sits relative to its stack.
+#[derive(clap::Parser, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainPosition {
+    /// Stack on the left, main on the right, sized by width
+    Right,
+    /// Stack as a strip along the top, main below it, sized by height
+    Bottom,
+}
+
+impl MainPosition {
+    /// The dimension `resize set` changes to size the main area.
+    pub fn dimension(self) -> &'static str {
+        match self {
+            Self::Right => "width",
+            Self::Bottom => "height",
+        }
+    }
+
+    /// The sway layout of a tiled stack, running across the main area.
+    pub fn stack_split(self) -> &'static str {
+        match self {
+            Self::Right => "splitv",
+            Self::Bottom => "splith",
+        }
+    }
+}
+
 #[derive(clap::Parser, Debug, Clone, PartialEq, Eq)]
 pub enum WorkspaceLayout {
     /// The spiral autotiling layout tiles windows in a spiral formation, similar to `AwesomeWM`
     Spiral,
+    /// The dwindle autotiling layout builds a binary tree where each new window splits the space of the window it opens next to
+    Dwindle,
     /// The `stack_main` autotiling layout keeps a stack of windows on the side of a larger main area, this layout comes with a few commands to control it as well
     StackMain {
         /// Size of the main area in percent
@@ -92,7 +343,84 @@ pub enum WorkspaceLayout {
         /// The sway layout of the stack: tabbed, tiled or stacked.
         #[arg(long, short = 'l', default_value_t = StackLayout::Stacked)]
         stack_layout: StackLayout,
+        /// Where the main area sits: right (stack on the left) or bottom (stack along the top).
+        #[arg(long, short = 'p', default_value_t = MainPosition::Right)]
+        main_position: MainPosition,
+    },
+    /// The `centered_main` autotiling layout keeps a main window in the middle with stacks of windows on both sides
+    CenteredMain {
+        /// Size of the main area in percent
+        #[arg(long, short = 's', value_parser = size_in_range, default_value_t = CENTERED_MAIN_DEFAULT_SIZE)]
+        size: u8,
     },
     /// The standard sway manual tiling
     Manual,
 }
+
+impl FromStr for Direction {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            s => Err(anyhow!("I don't know about the direction '{s}'")),
+        }
+    }
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let direction = match self {
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Left => "left",
+            Self::Right => "right",
+        };
+        write!(f, "{direction}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl FromStr for SplitDir {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "h" => Ok(Self::H),
+            "v" => Ok(Self::V),
+            "auto" => Ok(Self::Auto),
+            s => Err(anyhow!("I don't know about the split direction '{s}'")),
+        }
+    }
+}
+
+impl Display for SplitDir {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let dir = match self {
+            Self::H => "h",
+            Self::V => "v",
+            Self::Auto => "auto",
+        };
+        write!(f, "{dir}")
+    }
+}
+
+/// Split direction forced on the windows of a spiral workspace with `spiral-force-dir`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitDir {
+    /// Always `split h`, windows side by side
+    H,
+    /// Always `split v`, windows on top of each other
+    V,
+    /// Split along the longer side of the window
+    #[default]
+    Auto,
+}
@@ -0,0 +1,60 @@
+use env_logger::{Env, Logger};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the level was changed with `set-log-level`, from then on `RUST_LOG`
+/// no longer applies.
+static OVERRIDDEN: AtomicBool = AtomicBool::new(false);
+
+/// Logger whose level can be changed while the daemon runs.
+///
+/// `env_logger` fixes its filter when built, so records go through the filter of
+/// `RUST_LOG` until `set_level` is called, and through an unfiltered logger limited
+/// only by `log::max_level` after that.
+struct ReloadableLogger {
+    env: Logger,
+    all: Logger,
+}
+
+impl ReloadableLogger {
+    fn logger(&self) -> &Logger {
+        if OVERRIDDEN.load(Ordering::Relaxed) {
+            &self.all
+        } else {
+            &self.env
+        }
+    }
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.logger().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.logger().log(record);
+    }
+
+    fn flush(&self) {
+        self.logger().flush();
+    }
+}
+
+/// Install the logger, filtered by `RUST_LOG` (`info` when not set).
+pub fn init() {
+    let env = env_logger::Builder::from_env(Env::default().default_filter_or("info")).build();
+    let all = env_logger::Builder::new()
+        .filter_level(LevelFilter::Trace)
+        .build();
+    let max_level = env.filter();
+    let logger: &'static ReloadableLogger = Box::leak(Box::new(ReloadableLogger { env, all }));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Log everything at `level` and above from now on, replacing the `RUST_LOG` filter.
+pub fn set_level(level: LevelFilter) {
+    OVERRIDDEN.store(true, Ordering::Relaxed);
+    log::set_max_level(level);
+}
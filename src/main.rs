@@ -1,12 +1,13 @@
 #![allow(clippy::multiple_crate_versions)]
-use anyhow::Result;
-use env_logger::Env;
+use anyhow::{Result, anyhow};
 mod client;
 mod commands;
+mod config;
 mod layout;
+mod logging;
 mod node_ext;
 mod server;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
 mod utils;
 #[cfg(feature = "wallpaper")]
 mod wallpaper;
@@ -21,27 +22,48 @@ struct Args {
     #[command(subcommand)]
     command: commands::PerswayCommand,
     /// Path to control socket. This option applies both to daemon and client.
-    /// Defaults to <`XDG_RUNTIME_DIR>/persway`-<`WAYLAND_DISPLAY>.sock`>>
+    /// Defaults to <`XDG_RUNTIME_DIR>/persway`-<`WAYLAND_DISPLAY>.sock`>>.
+    /// The client also takes the address of a daemon started with `--listen`,
+    /// eg. `tcp:127.0.0.1:7070`.
     #[arg(long, short = 's')]
     socket_path: Option<String>,
+    /// Name of a persway instance, to run several daemons side by side (eg. one per
+    /// seat). Puts the name in the default socket path,
+    /// <`XDG_RUNTIME_DIR>/persway`-<`WAYLAND_DISPLAY>-<PROFILE>.sock`>>. Give the same
+    /// profile to the daemon and to its clients.
+    #[arg(long, short = 'P', value_name = "NAME", value_parser = utils::parse_profile)]
+    profile: Option<String>,
 }
 
 #[tokio::main]
 #[doc(hidden)]
 pub async fn main() -> Result<()> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let args = Args::parse();
+    logging::init();
+    let mut matches = Args::command().get_matches();
+    if let Some(("daemon", cli)) = matches.subcommand() {
+        let from_config = config::daemon_args(&commands::DaemonArgs::command(), cli)?;
+        if !from_config.is_empty() {
+            matches = Args::command()
+                .try_get_matches_from(std::env::args().chain(from_config))
+                .map_err(|e| {
+                    anyhow!("invalid config file setting ({}): {e}", config::PRECEDENCE)
+                })?;
+        }
+    }
+    let args = Args::from_arg_matches(&matches)?;
     if let commands::PerswayCommand::Daemon(daemon_args) = args.command {
-        server::daemon::Daemon::new(daemon_args, args.socket_path)
+        server::daemon::Daemon::new(*daemon_args, args.socket_path, args.profile.as_deref())
             .run()
             .await?;
     } else {
         log::debug!("command: {:?}", args.command);
-        client::send(
+        let body = client::send(
             args.socket_path,
+            args.profile.as_deref(),
             &std::env::args().collect::<Vec<_>>().join(" "),
         )
         .await?;
+        print!("{body}");
     }
     Ok(())
 }
@@ -58,6 +58,20 @@ pub trait NodeExt {
     fn is_full_screen(&self) -> bool;
     async fn is_stacked(&self) -> Result<bool>;
     async fn is_tabbed(&self) -> Result<bool>;
+    fn find_workspace_in<'a>(&self, tree: &'a Node) -> Option<&'a Node>;
+    fn find_parent_in<'a>(&self, tree: &'a Node) -> Option<&'a Node>;
+    fn is_tabbed_or_stacked_workspace(&self) -> bool;
+    fn app_id_or_class(&self) -> Option<&str>;
+    fn matches_app(&self, patterns: &[String]) -> bool;
+    fn is_transient(&self) -> bool;
+    fn inner_gap(&self) -> Option<i32>;
+}
+
+/// Match an app id against a pattern where a trailing `*` matches by prefix.
+pub fn app_pattern_matches(pattern: &str, app: &str) -> bool {
+    pattern
+        .strip_suffix('*')
+        .map_or_else(|| pattern == app, |prefix| app.starts_with(prefix))
 }
 
 impl NodeExt for Node {
@@ -86,6 +100,24 @@ impl NodeExt for Node {
             .ok_or_else(|| anyhow!(format!("couldn't find parent of node id: {}", self.id)))
     }
 
+    /// Like `get_workspace` but looks in an already fetched `tree`.
+    fn find_workspace_in<'a>(&self, tree: &'a Node) -> Option<&'a Node> {
+        let id = self.id;
+        tree.find_as_ref(|n| n.is_workspace() && n.iter().any(|c| c.id == id))
+    }
+
+    /// Like `get_parent` but looks in an already fetched `tree`.
+    fn find_parent_in<'a>(&self, tree: &'a Node) -> Option<&'a Node> {
+        let id = self.id;
+        tree.find_as_ref(|n| n.nodes.iter().any(|c| c.id == id))
+    }
+
+    /// Whether this is a workspace whose own layout was set to tabbed or stacked
+    /// (eg. `layout tabbed` on one of its windows), so layouts leave it alone.
+    fn is_tabbed_or_stacked_workspace(&self) -> bool {
+        self.is_workspace() && matches!(self.layout, NodeLayout::Tabbed | NodeLayout::Stacked)
+    }
+
     fn is_root(&self) -> bool {
         matches!(self.get_refined_node_type(), RefinedNodeType::Root)
     }
@@ -132,6 +164,52 @@ impl NodeExt for Node {
         Ok(parent.layout == NodeLayout::Tabbed)
     }
 
+    fn app_id_or_class(&self) -> Option<&str> {
+        self.app_id
+            .as_deref()
+            .filter(|id| !id.is_empty())
+            .or_else(|| {
+                self.window_properties
+                    .as_ref()
+                    .and_then(|p| p.class.as_deref())
+                    .filter(|class| !class.is_empty())
+            })
+    }
+
+    fn matches_app(&self, patterns: &[String]) -> bool {
+        self.app_id_or_class()
+            .is_some_and(|app| patterns.iter().any(|p| app_pattern_matches(p, app)))
+    }
+
+    /// Whether the window is a short-lived X11 dialog or splash screen: it has a
+    /// parent window (`transient_for`) or its window type says so. Wayland windows
+    /// carry no such properties, sway floats their dialogs by itself.
+    fn is_transient(&self) -> bool {
+        self.window_properties.as_ref().is_some_and(|props| {
+            props.transient_for.is_some()
+                || matches!(props.window_type.as_deref(), Some("dialog" | "splash"))
+        })
+    }
+
+    /// Inner gap in pixels between tiled containers, measured on the first split
+    /// container with more than one child, this node included. `None` if there is none.
+    fn inner_gap(&self) -> Option<i32> {
+        self.iter().find_map(|n| {
+            let count = i32::try_from(n.nodes.len())
+                .ok()
+                .filter(|&count| count > 1)?;
+            let (total, used) = match n.layout {
+                NodeLayout::SplitH => (n.rect.width, n.nodes.iter().map(|c| c.rect.width).sum()),
+                NodeLayout::SplitV => (
+                    n.rect.height,
+                    n.nodes.iter().map(|c| c.rect.height).sum::<i32>(),
+                ),
+                _ => return None,
+            };
+            Some(((total - used) / (count - 1)).max(0))
+        })
+    }
+
     fn get_refined_node_type(&self) -> RefinedNodeType {
         match self.node_type {
             NodeType::Root => RefinedNodeType::Root,
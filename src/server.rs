@@ -1,4 +1,8 @@
 mod command_handlers;
 pub mod daemon;
 mod event_handlers;
+mod events;
 mod message_handler;
+
+pub use event_handlers::misc::window_focus::FOCUS_FADE_MS;
+pub use event_handlers::misc::workspace_renamer::{DEFAULT_RENAMING_FORMAT, RENAMING_DELAY_MS};
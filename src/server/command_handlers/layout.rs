@@ -1 +1,3 @@
+pub mod manual;
+pub mod spatial;
 pub mod stack_main;
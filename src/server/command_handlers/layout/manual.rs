@@ -0,0 +1,106 @@
+use super::spatial::{offset, overlaps};
+use crate::{
+    layout::Direction,
+    node_ext::NodeExt,
+    utils::{get_focused_workspace, run_command},
+};
+use anyhow::{Result, bail};
+use swayipc_async::{Connection, Node, NodeLayout};
+
+pub struct Manual {
+    connection: Connection,
+    dry_run: bool,
+}
+
+/// The window next to `focused` in direction `dir`, wrapping around to the far
+/// side of the workspace when there is none.
+fn neighbor<'a>(windows: &[&'a Node], focused: &Node, dir: Direction) -> Option<&'a Node> {
+    let horizontal = matches!(dir, Direction::Left | Direction::Right);
+    let from = offset(&focused.rect, dir);
+    let in_line = windows
+        .iter()
+        .copied()
+        .filter(|n| n.id != focused.id && overlaps(&n.rect, &focused.rect, horizontal));
+    in_line
+        .clone()
+        .filter(|n| offset(&n.rect, dir) > from)
+        .min_by_key(|n| offset(&n.rect, dir))
+        .or_else(|| in_line.min_by_key(|n| offset(&n.rect, dir)))
+}
+
+impl Manual {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// Swap the focused window with its neighbor in direction `dir`.
+    ///
+    /// Neighbors are found by geometry among the visible tiled windows of the
+    /// workspace, at the edge of the workspace this wraps around.
+    pub async fn swap_direction(&mut self, dir: Direction) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let Some(wstree) = tree.find_as_ref(|n| n.id == ws.id) else {
+            bail!("focused workspace {} not found in the tree", ws.name);
+        };
+        let windows = wstree
+            .iter()
+            .filter(|n| n.is_window() && n.visible.unwrap_or(false))
+            .collect::<Vec<_>>();
+        let Some(focused) = windows.iter().find(|n| n.focused) else {
+            bail!("no focused tiled window");
+        };
+        let Some(other) = neighbor(&windows, focused, dir) else {
+            bail!("no window in direction {dir} of the focused window");
+        };
+        let cmd = format!(
+            "[con_id={}] swap container with con_id {}",
+            focused.id, other.id
+        );
+        log::debug!("manual controller, swap {dir}: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Rotate the focused workspace 90 degrees by flipping every split
+    /// between horizontal and vertical.
+    ///
+    /// Tabbed and stacked containers are left alone, splits nested in them are
+    /// still flipped.
+    pub async fn transpose(&mut self) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let Some(wstree) = tree.find_as_ref(|n| n.id == ws.id) else {
+            bail!("focused workspace {} not found in the tree", ws.name);
+        };
+        let cmds = wstree
+            .iter()
+            .filter(|n| matches!(n.layout, NodeLayout::SplitH | NodeLayout::SplitV))
+            .filter_map(|n| {
+                if n.is_container() {
+                    Some(n.id)
+                } else if n.is_workspace() {
+                    // The workspace can't be targeted itself, `layout` on one of
+                    // its windows changes the layout of the workspace instead
+                    let window = n.nodes.iter().find(|c| c.is_window());
+                    if window.is_none() {
+                        log::debug!("manual controller, transpose: no window directly on the workspace, keeping its split");
+                    }
+                    window.map(|c| c.id)
+                } else {
+                    None
+                }
+            })
+            .map(|id| format!("[con_id={id}] layout toggle split"))
+            .collect::<Vec<_>>();
+        if cmds.is_empty() {
+            return Ok(());
+        }
+        let cmd = cmds.join("; ");
+        log::debug!("manual controller, transpose: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+}
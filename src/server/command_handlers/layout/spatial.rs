@@ -0,0 +1,94 @@
+use crate::{
+    layout::Direction,
+    node_ext::NodeExt,
+    utils::{get_focused_workspace, run_command},
+};
+use anyhow::{Result, bail};
+use swayipc_async::{Connection, Node, Rect};
+
+pub struct Spatial {
+    connection: Connection,
+    dry_run: bool,
+}
+
+/// Whether `a` and `b` share some rows (`horizontal`) or columns (otherwise).
+pub fn overlaps(a: &Rect, b: &Rect, horizontal: bool) -> bool {
+    if horizontal {
+        a.y < b.y + b.height && b.y < a.y + a.height
+    } else {
+        a.x < b.x + b.width && b.x < a.x + a.width
+    }
+}
+
+/// Position of the center of `rect` along the axis of `dir`, growing towards `dir`.
+pub fn offset(rect: &Rect, dir: Direction) -> i32 {
+    match dir {
+        Direction::Right => rect.x + rect.width / 2,
+        Direction::Left => -(rect.x + rect.width / 2),
+        Direction::Down => rect.y + rect.height / 2,
+        Direction::Up => -(rect.y + rect.height / 2),
+    }
+}
+
+/// Distance between the centers of `a` and `b` across the axis of `dir`.
+fn offset_across(a: &Rect, b: &Rect, dir: Direction) -> i32 {
+    let across = match dir {
+        Direction::Left | Direction::Right => Direction::Down,
+        Direction::Up | Direction::Down => Direction::Right,
+    };
+    (offset(a, across) - offset(b, across)).abs()
+}
+
+/// The window nearest to `focused` in direction `dir`, by geometry alone.
+///
+/// Windows sharing rows (or columns) with `focused` come first, then the ones
+/// closest to it counting both along and across `dir`.
+fn nearest<'a>(windows: &[&'a Node], focused: &Node, dir: Direction) -> Option<&'a Node> {
+    let horizontal = matches!(dir, Direction::Left | Direction::Right);
+    let from = offset(&focused.rect, dir);
+    windows
+        .iter()
+        .copied()
+        .filter(|n| n.id != focused.id && offset(&n.rect, dir) > from)
+        .min_by_key(|n| {
+            (
+                !overlaps(&n.rect, &focused.rect, horizontal),
+                offset(&n.rect, dir) - from + offset_across(&n.rect, &focused.rect, dir),
+            )
+        })
+}
+
+impl Spatial {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// Focus the visible tiled window geometrically nearest to the focused one in
+    /// direction `dir`, whatever the shape of the tree.
+    ///
+    /// Fails when no window lies in that direction.
+    pub async fn focus(&mut self, dir: Direction) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let Some(wstree) = tree.find_as_ref(|n| n.id == ws.id) else {
+            bail!("focused workspace {} not found in the tree", ws.name);
+        };
+        let windows = wstree
+            .iter()
+            .filter(|n| n.is_window() && n.visible.unwrap_or(false))
+            .collect::<Vec<_>>();
+        let Some(focused) = windows.iter().find(|n| n.focused) else {
+            bail!("no focused tiled window");
+        };
+        let Some(other) = nearest(&windows, focused, dir) else {
+            bail!("no window {dir} of the focused window");
+        };
+        let cmd = format!("[con_id={}] focus", other.id);
+        log::debug!("spatial controller, focus {dir}: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+}
@@ -1,17 +1,25 @@
-use crate::{node_ext::NodeExt, utils::get_focused_workspace};
-use anyhow::Result;
+use crate::{
+    layout::{Direction, MainPosition, StackLayout, resize_amount},
+    node_ext::NodeExt,
+    utils::{get_focused_workspace, run_command},
+};
+use anyhow::{Context, Result};
 use either::Either;
 use std::fmt::Write;
-use swayipc_async::Connection;
+use swayipc_async::{Connection, NodeLayout};
 
 pub struct StackMain {
     connection: Connection,
+    dry_run: bool,
 }
 
 impl StackMain {
-    pub async fn new() -> Result<Self> {
+    pub async fn new(dry_run: bool) -> Result<Self> {
         let connection = Connection::new().await?;
-        Ok(Self { connection })
+        Ok(Self {
+            connection,
+            dry_run,
+        })
     }
 
     async fn stack_focus_advance(&mut self, reverse: bool) -> Result<()> {
@@ -53,7 +61,7 @@ impl StackMain {
                 if prev_was_focused {
                     let cmd = format!("[con_id={}] focus;", node.id);
                     log::debug!("stack main controller, stack focus prev: {cmd}");
-                    self.connection.run_command(cmd).await?;
+                    run_command(&mut self.connection, cmd, self.dry_run).await?;
                     return Ok(());
                 }
                 prev_was_focused = node.id == stack_current.id;
@@ -70,6 +78,53 @@ impl StackMain {
         self.stack_focus_advance(false).await
     }
 
+    /// Show the next (or with `reverse` the previous) window of a tabbed or stacked
+    /// stack, wrapping around, then focus the main window again.
+    ///
+    /// Does nothing for tiled stacks, all their windows show already.
+    async fn stack_page(&mut self, reverse: bool) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        let (Some(stack), Some(main)) = (wstree.nodes.first(), wstree.nodes.last()) else {
+            return Ok(());
+        };
+        if stack.nodes.is_empty()
+            || !matches!(stack.layout, NodeLayout::Tabbed | NodeLayout::Stacked)
+        {
+            return Ok(());
+        }
+
+        let count = stack.nodes.len();
+        let current = stack
+            .nodes
+            .iter()
+            .position(|n| {
+                n.visible.unwrap_or(false) || n.iter().any(|c| c.visible.unwrap_or(false))
+            })
+            .unwrap_or(0);
+        let next = if reverse {
+            (current + count - 1) % count
+        } else {
+            (current + 1) % count
+        };
+        let cmd = format!(
+            "[con_id={}] focus; [con_id={}] focus",
+            stack.nodes[next].id, main.id
+        );
+        log::debug!("stack main controller, stack page: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    pub async fn stack_page_next(&mut self) -> Result<()> {
+        self.stack_page(false).await
+    }
+
+    pub async fn stack_page_prev(&mut self) -> Result<()> {
+        self.stack_page(true).await
+    }
+
     pub async fn stack_main_rotate(&mut self, reverse: bool) -> Result<()> {
         let tree = self.connection.get_tree().await?;
         let ws = get_focused_workspace(&mut self.connection).await?;
@@ -130,7 +185,7 @@ impl StackMain {
             );
 
             log::debug!("stack main controller, master cycle next 1: {cmd}");
-            self.connection.run_command(cmd).await?;
+            run_command(&mut self.connection, cmd, self.dry_run).await?;
 
             let tree = self.connection.get_tree().await?;
             let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
@@ -167,7 +222,7 @@ impl StackMain {
                 )
             };
             log::debug!("stack main controller, master cycle next 2: {cmd}");
-            self.connection.run_command(cmd).await?;
+            run_command(&mut self.connection, cmd, self.dry_run).await?;
             return Ok(());
         }
         Ok(())
@@ -181,14 +236,18 @@ impl StackMain {
         self.stack_main_rotate(true).await
     }
 
-    pub async fn stack_swap_main(&mut self) -> Result<()> {
+    /// Swap the focused (or visible) stack window with the main window.
+    ///
+    /// Returns the ids of the old main window and of the window that took its place,
+    /// `None` if the stack is empty.
+    pub async fn stack_swap_main(&mut self) -> Result<Option<(i64, i64)>> {
         let tree = self.connection.get_tree().await?;
         let ws = get_focused_workspace(&mut self.connection).await?;
         let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
 
         if let Some(stack) = wstree.nodes.first() {
             if stack.nodes.is_empty() {
-                return Ok(());
+                return Ok(None);
             }
 
             let main = wstree.nodes.last().expect("main window not found");
@@ -212,7 +271,273 @@ impl StackMain {
                 main.id, stack_current.id, stack_current.id
             );
             log::debug!("stack main controller, swap visible: {cmd}");
-            self.connection.run_command(cmd).await?;
+            run_command(&mut self.connection, cmd, self.dry_run).await?;
+            return Ok(Some((main.id, stack_current.id)));
+        }
+        Ok(None)
+    }
+
+    /// Swap the most recently focused stack window, by `focus_history` (oldest first),
+    /// with the main window, wherever the focus is now.
+    ///
+    /// Returns the ids of the old main window and of the window that took its place,
+    /// `None` if the stack is empty. Fails when no stack window was focused yet.
+    pub async fn stack_swap_last_focused(
+        &mut self,
+        focus_history: &[i64],
+    ) -> Result<Option<(i64, i64)>> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        let (Some(stack), Some(main)) = (wstree.nodes.first(), wstree.nodes.last()) else {
+            return Ok(None);
+        };
+        if stack.nodes.is_empty() || wstree.nodes.len() < 2 {
+            return Ok(None);
+        }
+        let last_focused = focus_history
+            .iter()
+            .rev()
+            .find(|&&id| stack.iter().any(|n| n.is_window() && n.id == id))
+            .context("no window of the stack was focused yet")?;
+
+        let cmd = format!(
+            "[con_id={}] focus; swap container with con_id {last_focused}; \
+             [con_id={last_focused}] focus",
+            main.id
+        );
+        log::debug!("stack main controller, swap last focused: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await?;
+        Ok(Some((main.id, *last_focused)))
+    }
+
+    /// Undo `stack_swap_main`: put `main` back in the main area and `other` back
+    /// in the stack, focusing `other` again.
+    pub async fn stack_unswap_main(&mut self, main: i64, other: i64) -> Result<()> {
+        let cmd = format!(
+            "[con_id={main}] focus; swap container with con_id {other}; [con_id={other}] focus"
+        );
+        log::debug!("stack main controller, undo swap: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Move the focused window one step in `dir` without leaving the main/stack shape.
+    ///
+    /// Along the stack (up/down with the main area on the right, left/right with it at
+    /// the bottom) the window swaps places with its neighbor in the stack. Towards the
+    /// main area a stack window swaps with the main window, away from it the main window
+    /// swaps with the visible stack window. Does nothing at the edges.
+    pub async fn stack_move(&mut self, dir: Direction, main_position: MainPosition) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        let (Some(stack), Some(main)) = (wstree.nodes.first(), wstree.nodes.last()) else {
+            return Ok(());
+        };
+        if stack.nodes.is_empty() || wstree.nodes.len() < 2 {
+            return Ok(());
+        }
+        let (prev, next, to_main, to_stack) = match main_position {
+            MainPosition::Right => (
+                Direction::Up,
+                Direction::Down,
+                Direction::Right,
+                Direction::Left,
+            ),
+            MainPosition::Bottom => (
+                Direction::Left,
+                Direction::Right,
+                Direction::Down,
+                Direction::Up,
+            ),
+        };
+        let focused = stack
+            .nodes
+            .iter()
+            .position(|n| n.focused || n.find_as_ref(|c| c.focused).is_some());
+
+        let (moved, target) = match focused {
+            Some(index) if dir == prev => match index.checked_sub(1) {
+                Some(before) => (stack.nodes[index].id, stack.nodes[before].id),
+                None => return Ok(()),
+            },
+            Some(index) if dir == next => match stack.nodes.get(index + 1) {
+                Some(after) => (stack.nodes[index].id, after.id),
+                None => return Ok(()),
+            },
+            Some(index) if dir == to_main => (stack.nodes[index].id, main.id),
+            None if dir == to_stack && main.find_as_ref(|n| n.focused).is_some() => {
+                let visible = stack
+                    .nodes
+                    .iter()
+                    .find(|n| {
+                        n.visible.unwrap_or(false) || n.iter().any(|c| c.visible.unwrap_or(false))
+                    })
+                    .unwrap_or(&stack.nodes[0]);
+                (main.id, visible.id)
+            }
+            _ => return Ok(()),
+        };
+        let cmd = format!(
+            "[con_id={moved}] focus; swap container with con_id {target}; [con_id={moved}] focus"
+        );
+        log::debug!("stack main controller, move {dir}: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Resize the main area of the focused workspace to `size` percent of its width,
+    /// or of its height when the main area sits at the bottom.
+    ///
+    /// Does nothing while the workspace has no stack, the main window fills it then.
+    /// With `gap_aware`, sizes in pixels between the inner gaps, see `resize_amount`.
+    pub async fn stack_main_set_size(
+        &mut self,
+        size: u8,
+        main_position: MainPosition,
+        gap_aware: bool,
+    ) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        if wstree.nodes.len() < 2 {
+            return Ok(());
+        }
+        let main = wstree.nodes.last().expect("main window not found");
+        let total = match main_position {
+            MainPosition::Right => wstree.rect.width,
+            MainPosition::Bottom => wstree.rect.height,
+        };
+        let gap = if gap_aware { wstree.inner_gap() } else { None };
+        let cmd = format!(
+            "[con_id={}] resize set {} {}",
+            main.id,
+            main_position.dimension(),
+            resize_amount(size, total, 2, gap)
+        );
+        log::debug!("stack main controller, set main size: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Bring the focused window into the main area, unless it is there already, and
+    /// resize the main area to `size` percent.
+    pub async fn stack_zoom(
+        &mut self,
+        size: u8,
+        main_position: MainPosition,
+        gap_aware: bool,
+    ) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        let focused_in_stack = wstree
+            .nodes
+            .first()
+            .filter(|stack| !stack.nodes.is_empty())
+            .and_then(|stack| stack.find_as_ref(|n| n.is_window() && n.focused))
+            .is_some();
+        if focused_in_stack {
+            self.stack_swap_main().await?;
+        }
+        self.stack_main_set_size(size, main_position, gap_aware)
+            .await
+    }
+
+    /// Give every window of a tiled stack the same share of the stack.
+    ///
+    /// Does nothing for tabbed and stacked stacks, only one of their windows shows.
+    /// With `gap_aware`, sizes in pixels between the inner gaps, see `resize_amount`.
+    pub async fn stack_balance(&mut self, gap_aware: bool) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        let Some(stack) = wstree.nodes.first().filter(|stack| !stack.nodes.is_empty()) else {
+            return Ok(());
+        };
+        let (dimension, total) = match stack.layout {
+            NodeLayout::SplitV => ("height", stack.rect.height),
+            NodeLayout::SplitH => ("width", stack.rect.width),
+            _ => return Ok(()),
+        };
+        let count = i32::try_from(stack.nodes.len())?;
+        let share = u8::try_from(100 / count)?;
+        let gap = if gap_aware { stack.inner_gap() } else { None };
+        let amount = resize_amount(share, total, count, gap);
+        // The last window gets what is left
+        let cmd = stack
+            .nodes
+            .iter()
+            .take(stack.nodes.len() - 1)
+            .map(|n| format!("[con_id={}] resize set {dimension} {amount}", n.id))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if cmd.is_empty() {
+            return Ok(());
+        }
+        log::debug!("stack main controller, balance stack: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Grow (or with `shrink`, shrink) the focused window of a tiled stack by `amount`
+    /// percent along the stack.
+    ///
+    /// Fails when the focused window isn't in the stack. Does nothing for tabbed and
+    /// stacked stacks, their windows all take the whole stack.
+    pub async fn stack_resize(&mut self, shrink: bool, amount: u8) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        let focused = wstree
+            .nodes
+            .first()
+            .filter(|_| wstree.nodes.len() > 1)
+            .and_then(|stack| {
+                let focused = stack
+                    .nodes
+                    .iter()
+                    .find(|n| n.focused || n.find_as_ref(|c| c.focused).is_some())?;
+                Some((stack, focused))
+            });
+        let (stack, focused) = focused.context("the focused window is not in the stack")?;
+        let dimension = match stack.layout {
+            NodeLayout::SplitV => "height",
+            NodeLayout::SplitH => "width",
+            _ => return Ok(()),
+        };
+        let cmd = format!(
+            "[con_id={}] resize {} {dimension} {amount} ppt",
+            focused.id,
+            if shrink { "shrink" } else { "grow" }
+        );
+        log::debug!("stack main controller, resize stack window: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Change the sway layout of the existing stack container without moving any windows.
+    pub async fn stack_set_layout(
+        &mut self,
+        stack_layout: &StackLayout,
+        main_position: MainPosition,
+    ) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+
+        // `layout` applies to the parent of the targeted container, so target a stack leaf.
+        if let Some(leaf) = wstree.nodes.first().and_then(|stack| stack.nodes.first()) {
+            let layout = match stack_layout {
+                StackLayout::Tabbed => "tabbed",
+                StackLayout::Stacked => "stacking",
+                StackLayout::Tiled => main_position.stack_split(),
+            };
+            let cmd = format!("[con_id={}] layout {layout}", leaf.id);
+            log::debug!("stack main controller, set stack layout: {cmd}");
+            run_command(&mut self.connection, cmd, self.dry_run).await?;
         }
         Ok(())
     }
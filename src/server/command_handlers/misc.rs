@@ -0,0 +1,7 @@
+pub mod flatten;
+pub mod marks;
+pub mod pin;
+pub mod scratch;
+pub mod snapshot;
+pub mod stash;
+pub mod swap;
@@ -0,0 +1,55 @@
+use crate::{
+    node_ext::NodeExt,
+    utils::{get_focused_workspace, run_command},
+};
+use anyhow::{Result, bail};
+use swayipc_async::{Connection, NodeLayout};
+
+pub struct Flatten {
+    connection: Connection,
+    dry_run: bool,
+}
+
+impl Flatten {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// Remove the split containers of the focused workspace that hold a single child,
+    /// moving the child up in their place. Nested ones collapse all the way up.
+    ///
+    /// Tabbed and stacked containers are kept even with one child, and so is the
+    /// first container of the workspace with `keep_stack`, the stack of `stack_main`.
+    ///
+    /// Returns how many containers were removed.
+    pub async fn workspace(&mut self, keep_stack: bool) -> Result<usize> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let Some(wstree) = tree.find_as_ref(|n| n.id == ws.id) else {
+            bail!("focused workspace {} not found in the tree", ws.name);
+        };
+        let stack_id = wstree.nodes.first().filter(|_| keep_stack).map(|n| n.id);
+        // `split none` on the only child of a split container removes the container
+        let cmds = wstree
+            .iter()
+            .filter(|n| {
+                n.is_container()
+                    && n.nodes.len() == 1
+                    && matches!(n.layout, NodeLayout::SplitH | NodeLayout::SplitV)
+                    && Some(n.id) != stack_id
+            })
+            .map(|n| format!("[con_id={}] split none", n.nodes[0].id))
+            .collect::<Vec<_>>();
+        if cmds.is_empty() {
+            return Ok(0);
+        }
+        let cmd = cmds.join("; ");
+        log::debug!("flatten controller, flatten workspace: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await?;
+        Ok(cmds.len())
+    }
+}
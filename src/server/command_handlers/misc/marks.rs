@@ -0,0 +1,82 @@
+use crate::{node_ext::NodeExt, utils::run_command};
+use anyhow::Result;
+use swayipc_async::{Connection, Node};
+
+/// Prefix of the marks the stack layouts use to move windows into a stack.
+const STACK_MARK_PREFIX: &str = "_stack_";
+
+pub struct Marks {
+    connection: Connection,
+    dry_run: bool,
+}
+
+/// Every `_stack_*` mark in `tree` with the node carrying it.
+fn stack_marks(tree: &Node) -> Vec<(&str, &Node)> {
+    tree.iter()
+        .flat_map(|n| {
+            n.marks
+                .iter()
+                .filter(|m| m.starts_with(STACK_MARK_PREFIX))
+                .map(move |m| (m.as_str(), n))
+        })
+        .collect()
+}
+
+/// Whether `mark` still names an active stack: a split container, directly on a
+/// workspace, with the id the mark was made from.
+fn is_active_stack_mark(tree: &Node, mark: &str) -> bool {
+    let Some(id) = mark
+        .strip_prefix(STACK_MARK_PREFIX)
+        .and_then(|id| id.parse::<i64>().ok())
+    else {
+        return false;
+    };
+    tree.find_as_ref(|n| n.id == id).is_some_and(|stack| {
+        !stack.nodes.is_empty()
+            && stack
+                .find_parent_in(tree)
+                .is_some_and(NodeExt::is_workspace)
+    })
+}
+
+impl Marks {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// The `_stack_*` marks in the tree, one `<mark> con_id=<id>` line each.
+    pub async fn list(&mut self) -> Result<Vec<String>> {
+        let tree = self.connection.get_tree().await?;
+        Ok(stack_marks(&tree)
+            .into_iter()
+            .map(|(mark, node)| format!("{mark} con_id={}", node.id))
+            .collect())
+    }
+
+    /// Remove the `_stack_*` marks that no longer name an active stack, returning them.
+    ///
+    /// The stack layouts remove their marks in the same command that sets them, so
+    /// these are leftovers of a command that failed halfway.
+    pub async fn clean(&mut self) -> Result<Vec<String>> {
+        let tree = self.connection.get_tree().await?;
+        let stale = stack_marks(&tree)
+            .into_iter()
+            .filter(|(mark, _)| !is_active_stack_mark(&tree, mark))
+            .collect::<Vec<_>>();
+        if stale.is_empty() {
+            return Ok(Vec::new());
+        }
+        let cmd = stale
+            .iter()
+            .map(|(mark, node)| format!("[con_id={}] unmark {mark}", node.id))
+            .collect::<Vec<_>>()
+            .join("; ");
+        log::debug!("marks controller, clean: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await?;
+        Ok(stale.into_iter().map(|(mark, _)| mark.to_owned()).collect())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{node_ext::NodeExt, utils::run_command};
+use anyhow::{Context, Result};
+use swayipc_async::Connection;
+
+pub struct Pin {
+    connection: Connection,
+    dry_run: bool,
+}
+
+impl Pin {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// Pin the focused window, floating, centered and sticky so it stays in view on
+    /// every workspace, or put it back in the tiling if `pinned` holds it already.
+    ///
+    /// `pinned` is updated, closed windows are dropped from it along the way.
+    ///
+    /// Returns the id of the window and whether it is pinned now.
+    pub async fn toggle(&mut self, pinned: &mut Vec<i64>) -> Result<(i64, bool)> {
+        let tree = self.connection.get_tree().await?;
+        pinned.retain(|&id| tree.find_as_ref(|n| n.id == id).is_some());
+        let focused = tree
+            .find_as_ref(|n| n.focused && (n.is_window() || n.is_floating_window()))
+            .context("no focused window to pin")?;
+        let id = focused.id;
+
+        let pin = !pinned.contains(&id);
+        let cmd = if pin {
+            format!("[con_id={id}] floating enable, sticky enable, move position center")
+        } else {
+            format!("[con_id={id}] sticky disable, floating disable")
+        };
+        log::debug!("pin controller, toggle: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await?;
+
+        if pin {
+            pinned.push(id);
+        } else {
+            pinned.retain(|&p| p != id);
+        }
+        Ok((id, pin))
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    node_ext::NodeExt,
+    utils::{SCRATCHPAD_WORKSPACE, get_focused_workspace, run_command},
+};
+use anyhow::{Result, bail};
+use swayipc_async::Connection;
+
+pub struct Scratch {
+    connection: Connection,
+    dry_run: bool,
+}
+
+impl Scratch {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// Toggle the window marked `mark` in and out of view on the focused workspace.
+    ///
+    /// A window in the scratchpad is shown, one visible on the focused workspace is
+    /// moved to the scratchpad and one on another workspace is brought over. When no
+    /// window has the mark yet, the focused window gets it.
+    pub async fn toggle(&mut self, mark: &str) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let Some(marked) = tree.find_as_ref(|n| n.marks.iter().any(|m| m == mark)) else {
+            let Some(focused) =
+                tree.find_as_ref(|n| n.focused && (n.is_window() || n.is_floating_window()))
+            else {
+                bail!("no window marked '{mark}' and no focused window to mark");
+            };
+            let cmd = format!("[con_id={}] mark --add \"{mark}\"", focused.id);
+            log::debug!("scratch controller, mark: {cmd}");
+            return run_command(&mut self.connection, cmd, self.dry_run).await;
+        };
+        let workspace = marked.find_workspace_in(&tree);
+        let in_scratchpad =
+            workspace.is_none_or(|w| w.name.as_deref() == Some(SCRATCHPAD_WORKSPACE));
+        let on_focused = workspace.is_some_and(|w| w.id == ws.id);
+        let cmd = if in_scratchpad {
+            format!("[con_mark=\"{mark}\"] scratchpad show")
+        } else if on_focused && marked.visible.unwrap_or(false) {
+            format!("[con_mark=\"{mark}\"] move scratchpad")
+        } else {
+            format!("[con_mark=\"{mark}\"] move scratchpad; [con_mark=\"{mark}\"] scratchpad show")
+        };
+        log::debug!("scratch controller, toggle: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+}
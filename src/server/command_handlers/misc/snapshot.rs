@@ -0,0 +1,142 @@
+use crate::{
+    layout::{WorkspaceLayout, parse_layout_args},
+    node_ext::NodeExt,
+};
+use anyhow::{Context, Result, anyhow, ensure};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use swayipc_async::Node;
+
+/// A window of a snapshot.
+#[derive(Debug, Clone)]
+pub struct SnapshotWindow {
+    pub id: i64,
+    /// `app_id`/class, only to tell which window went missing on restore.
+    pub app: String,
+    /// `main`, `stack` or `tiled`.
+    pub role: &'static str,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The arrangement of a workspace saved with `save-snapshot`.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub layout: WorkspaceLayout,
+    /// Tiled windows in tree order, the main window of `stack_main` last.
+    pub windows: Vec<SnapshotWindow>,
+}
+
+/// Directory snapshots are stored in, `$XDG_STATE_HOME/persway`
+/// (`~/.local/state/persway` if not set).
+fn snapshot_dir() -> Result<PathBuf> {
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .context("neither XDG_STATE_HOME nor HOME is set")?;
+    Ok(state.join("persway"))
+}
+
+/// Path of the snapshot `name`.
+fn snapshot_path(name: &str) -> Result<PathBuf> {
+    ensure!(
+        !name.is_empty() && !name.contains('/') && !name.starts_with('.'),
+        "invalid snapshot name '{name}'"
+    );
+    Ok(snapshot_dir()?.join(format!("{name}.json")))
+}
+
+impl Snapshot {
+    /// Capture the tiled windows of workspace `ws` from the tree, laid out as `layout`.
+    pub fn capture(ws: &Node, layout: WorkspaceLayout) -> Self {
+        let main = match layout {
+            WorkspaceLayout::StackMain { .. } => ws.nodes.last().filter(|n| n.is_window()),
+            _ => None,
+        };
+        let mut windows = ws
+            .iter()
+            .filter(|n| n.is_window())
+            .map(|n| SnapshotWindow {
+                id: n.id,
+                app: n.app_id_or_class().unwrap_or("unknown").to_owned(),
+                role: match main {
+                    Some(main) if main.id == n.id => "main",
+                    Some(_) => "stack",
+                    None => "tiled",
+                },
+                width: n.rect.width,
+                height: n.rect.height,
+            })
+            .collect::<Vec<_>>();
+        // The main window goes last so it is moved back last, which puts it in main.
+        windows.sort_by_key(|w| w.role == "main");
+        Self { layout, windows }
+    }
+
+    /// Write the snapshot to the file of `name`, replacing any previous one.
+    ///
+    /// Returns the path of the file.
+    pub fn save(&self, name: &str) -> Result<PathBuf> {
+        let path = snapshot_path(name)?;
+        let windows = self
+            .windows
+            .iter()
+            .map(|w| {
+                json!({
+                    "id": w.id,
+                    "app": w.app,
+                    "role": w.role,
+                    "width": w.width,
+                    "height": w.height,
+                })
+            })
+            .collect::<Vec<_>>();
+        let contents = json!({
+            "layout": self.layout.to_args(),
+            "windows": windows,
+        });
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        std::fs::write(&path, format!("{contents:#}\n"))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Read the snapshot `name`.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = snapshot_path(name)?;
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("no snapshot named {name} ({})", path.display()))?;
+        let value: Value = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        let layout = value["layout"].as_str().context("snapshot has no layout")?;
+        let layout = parse_layout_args(layout).map_err(|e| anyhow!("snapshot layout: {e}"))?;
+        let windows = value["windows"]
+            .as_array()
+            .context("snapshot has no windows")?
+            .iter()
+            .map(|w| {
+                Ok(SnapshotWindow {
+                    id: w["id"].as_i64().context("snapshot window has no id")?,
+                    app: w["app"].as_str().unwrap_or("unknown").to_owned(),
+                    role: match w["role"].as_str() {
+                        Some("main") => "main",
+                        Some("stack") => "stack",
+                        _ => "tiled",
+                    },
+                    width: w["width"]
+                        .as_i64()
+                        .and_then(|v| i32::try_from(v).ok())
+                        .unwrap_or_default(),
+                    height: w["height"]
+                        .as_i64()
+                        .and_then(|v| i32::try_from(v).ok())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { layout, windows })
+    }
+}
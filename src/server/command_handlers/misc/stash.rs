@@ -0,0 +1,66 @@
+use crate::{node_ext::NodeExt, utils::run_command};
+use anyhow::{Context, Result};
+use swayipc_async::Connection;
+
+pub struct Stash {
+    connection: Connection,
+    dry_run: bool,
+}
+
+impl Stash {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// Move the focused window to a new workspace, numbered one past the highest
+    /// numbered workspace so it stays out of the way. Focus stays where it is.
+    ///
+    /// Returns the id of the stashed window and the number of its workspace.
+    pub async fn stash(&mut self) -> Result<(i64, i32)> {
+        let tree = self.connection.get_tree().await?;
+        let focused = tree
+            .find_as_ref(|n| n.focused && (n.is_window() || n.is_floating_window()))
+            .context("no focused window to stash")?;
+        let number = self
+            .connection
+            .get_workspaces()
+            .await?
+            .iter()
+            .map(|ws| ws.num)
+            .max()
+            .unwrap_or_default()
+            .max(0)
+            + 1;
+        let cmd = format!(
+            "[con_id={}] move container to workspace number {number}",
+            focused.id
+        );
+        log::debug!("stash controller, stash: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await?;
+        Ok((focused.id, number))
+    }
+
+    /// Bring the most recently stashed window of `stashed` that still exists back to
+    /// the focused workspace and focus it, dropping the closed ones from `stashed`.
+    ///
+    /// Returns the id of the window, `None` if every stashed window was closed.
+    pub async fn unstash(&mut self, stashed: &mut Vec<i64>) -> Result<Option<i64>> {
+        let tree = self.connection.get_tree().await?;
+        while let Some(id) = stashed.pop() {
+            if tree.find_as_ref(|n| n.id == id).is_none() {
+                log::debug!("stash controller, window {id} was closed while stashed");
+                continue;
+            }
+            let cmd =
+                format!("[con_id={id}] move container to workspace current; [con_id={id}] focus");
+            log::debug!("stash controller, unstash: {cmd}");
+            run_command(&mut self.connection, cmd, self.dry_run).await?;
+            return Ok(Some(id));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::utils::run_command;
+use anyhow::{Result, bail, ensure};
+use swayipc_async::Connection;
+
+pub struct Swap {
+    connection: Connection,
+    dry_run: bool,
+}
+
+impl Swap {
+    pub async fn new(dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            dry_run,
+        })
+    }
+
+    /// Swap the windows marked `a` and `b`, wherever they are.
+    ///
+    /// Fails without changing anything when either mark isn't on any window.
+    pub async fn marks(&mut self, a: &str, b: &str) -> Result<()> {
+        ensure!(a != b, "can't swap the window marked '{a}' with itself");
+        let tree = self.connection.get_tree().await?;
+        let find = |mark: &str| tree.find_as_ref(|n| n.marks.iter().any(|m| m == mark));
+        let (first, second) = match (find(a), find(b)) {
+            (Some(first), Some(second)) => (first, second),
+            (None, Some(_)) => bail!("no window marked '{a}'"),
+            (Some(_), None) => bail!("no window marked '{b}'"),
+            (None, None) => bail!("no windows marked '{a}' or '{b}'"),
+        };
+        let cmd = format!(
+            "[con_id={}] swap container with con_id {}",
+            first.id, second.id
+        );
+        log::debug!("swap controller, swap marks: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+}
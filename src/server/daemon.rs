@@ -1,42 +1,112 @@
 //! Persway daemon module.
 //!
 //! Manages:
-//! - A Unix socket for receiving CLI commands.
+//! - A Unix (or, with `--listen`, abstract or TCP) socket for receiving CLI commands.
 //! - Sway IPC event subscription and handling.
 //! - Signal handling for graceful shutdown.
 //! - Per‑workspace layout management via `MessageHandler`.
 
-use super::message_handler::MessageHandler;
+use super::event_handlers::misc::workspace_renamer::RenamerConfig;
+use super::events::{self, EVENTS_CAPACITY, EventSender, PerswayEvent};
+use super::message_handler::{CommandError, MessageHandler};
 use crate::Args;
 use crate::commands::PerswayCommand;
 use crate::layout::WorkspaceLayout;
+use crate::utils::{ControlStream, ListenAddr};
 #[cfg(feature = "wallpaper")]
 use crate::wallpaper;
 use crate::{commands::DaemonArgs, utils};
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
 use clap::Parser;
 use futures::SinkExt;
 use futures::channel::mpsc;
-use futures::{select, stream::StreamExt};
+use futures::{
+    select,
+    stream::{Fuse, StreamExt},
+};
 use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
 use signal_hook_tokio::Signals;
 #[cfg(feature = "wallpaper")]
 use std::collections::HashMap;
 use std::process::exit;
-use swayipc_async::{Connection, Event, EventType};
-use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::oneshot;
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+use swayipc_async::{Connection, Event, EventStream, EventType};
+use tokio::io::WriteHalf;
+use tokio::net::{TcpListener, UnixListener, UnixStream};
+use tokio::sync::{broadcast, oneshot};
 
 /// Generic sender type for cross‑task messaging.
 pub type Sender<T> = mpsc::UnboundedSender<T>;
 
+/// Reply to a CLI command: `Some` carries a (possibly multi-line) data payload.
+pub type Reply = anyhow::Result<Option<String>>;
+
+/// How long outputs have to be quiet before layouts are re-seeded, docking a
+/// laptop fires a burst of output events.
+const OUTPUT_SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// How long events subscribers get to receive the shutdown event before the daemon exits.
+const SHUTDOWN_EVENT_GRACE: Duration = Duration::from_millis(50);
+
+/// Consecutive Sway event errors after which the connection is taken as lost.
+const MAX_EVENT_ERRORS: u32 = 5;
+
+/// First and longest wait between two attempts to reconnect to Sway.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Writing half of a control socket connection.
+type ControlWriter = WriteHalf<Box<dyn ControlStream>>;
+
+/// Listener of the control socket, see `--listen`.
+enum ControlListener {
+    /// Socket file or abstract socket.
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+impl ControlListener {
+    async fn bind(addr: &ListenAddr) -> Result<Self> {
+        Ok(match addr {
+            ListenAddr::Unix(path) => Self::Unix(UnixListener::bind(path)?),
+            ListenAddr::Abstract(name) => {
+                let listener = std::os::unix::net::UnixListener::bind_addr(
+                    &utils::abstract_socket_addr(name)?,
+                )?;
+                listener.set_nonblocking(true)?;
+                Self::Unix(UnixListener::from_std(listener)?)
+            }
+            ListenAddr::Tcp(addr) => {
+                if !addr.ip().is_loopback() {
+                    log::warn!("Control socket on {addr} is reachable from other machines");
+                }
+                Self::Tcp(TcpListener::bind(addr).await?)
+            }
+        })
+    }
+
+    async fn accept(&self) -> std::io::Result<Box<dyn ControlStream>> {
+        Ok(match self {
+            Self::Unix(listener) => Box::new(listener.accept().await?.0),
+            Self::Tcp(listener) => Box::new(listener.accept().await?.0),
+        })
+    }
+}
+
 /// Message type sent over the internal channel.
 ///
-/// Currently only used for CLI commands coming from the Unix socket.
+/// Used for CLI commands coming from the Unix socket and for deferred work.
 #[derive(Debug)]
 pub enum Message {
     /// A command received from the `persway` CLI client.
-    CommandEvent(PerswayCommand, oneshot::Sender<anyhow::Result<()>>),
+    CommandEvent(PerswayCommand, oneshot::Sender<Reply>),
+    /// No output event arrived for `OUTPUT_SETTLE_DELAY`.
+    OutputsSettled,
+    /// `persway quit` was answered, shut the daemon down.
+    Quit,
+    /// Time to check workspaces for layout drift (`--reconcile-interval-secs`).
+    Reconcile,
 }
 
 /// Persway daemon state.
@@ -48,19 +118,25 @@ pub enum Message {
 pub struct Daemon {
     /// Optional command to run when the daemon exits (e.g., reset opacity).
     on_exit: Option<String>,
-    /// Path to the Unix socket used for CLI IPC.
-    socket_path: String,
+    /// Where the socket used for CLI IPC listens.
+    listen: ListenAddr,
+    /// When the daemon was started, reported by `persway status`.
+    started: Instant,
+    /// Path to the Unix socket that state change events are broadcast on.
+    events_socket_path: String,
+    /// How often workspaces are checked for layout drift, if at all.
+    reconcile_interval: Option<Duration>,
+    /// How long a control socket connection may wait between commands, if limited.
+    client_idle_timeout: Option<Duration>,
     /// Message handler that manages workspaces and layouts.
     ///
     /// Wrapped in `Option` to allow async initialization in `run()`.
     message_handler: Option<MessageHandler>,
-    /// Temporary storage of constructor arguments until `run()` is called.
+    /// Temporary storage of the daemon arguments until `run()` is called.
     ///
-    /// Holds:
-    /// - The default layout for new workspaces.
-    /// - Whether workspace renaming is enabled.
-    /// - Focus/leave hooks for opacity or marking.
-    init_args: Option<(WorkspaceLayout, bool, Option<String>, Option<String>)>,
+    /// Holds the default layout for new workspaces, whether workspace renaming
+    /// is enabled, focus/leave hooks and the layout ignore list.
+    init_args: Option<DaemonArgs>,
 
     #[cfg(feature = "wallpaper")]
     wallpaper_handles: HashMap<String, wallpaper::WallpaperHandle>,
@@ -70,54 +146,140 @@ impl Daemon {
     /// Construct a new `Daemon` from CLI arguments.
     ///
     /// The `message_handler` is left uninitialized; it will be created in `run()`.
-    pub fn new(args: DaemonArgs, socket_path: Option<String>) -> Self {
-        let socket_path = utils::get_socket_path(socket_path);
-        let DaemonArgs {
-            default_layout,
-            stack_main_default_size,
-            stack_main_default_stack_layout,
-            workspace_renaming,
-            on_window_focus,
-            on_window_focus_leave,
-            on_exit,
-            ..
-        } = args;
-
-        let final_layout = match default_layout {
-            WorkspaceLayout::StackMain { .. } => WorkspaceLayout::StackMain {
-                size: stack_main_default_size,
-                stack_layout: stack_main_default_stack_layout,
-            },
-            _ => default_layout,
-        };
+    pub fn new(mut args: DaemonArgs, socket_path: Option<String>, profile: Option<&str>) -> Self {
+        let listen = args.listen.take().unwrap_or_else(|| {
+            ListenAddr::Unix(utils::get_socket_path(socket_path.clone(), profile))
+        });
+        // The events socket goes next to the control socket file, if there is one
+        let events_socket_path = utils::get_events_socket_path(&match &listen {
+            ListenAddr::Unix(path) => path.clone(),
+            _ => utils::get_socket_path(socket_path, profile),
+        });
+
+        let layouts = std::iter::once(&mut args.default_layout)
+            .chain(args.output_layout.iter_mut().map(|(_, layout)| layout))
+            .chain(
+                args.workspace_layout_rule
+                    .iter_mut()
+                    .map(|(_, layout)| layout),
+            )
+            .chain(args.app_layout.iter_mut().map(|(_, layout)| layout));
+        for layout in layouts {
+            if let WorkspaceLayout::StackMain { .. } = layout {
+                *layout = WorkspaceLayout::StackMain {
+                    size: args.stack_main_default_size,
+                    stack_layout: args.stack_main_default_stack_layout.clone(),
+                    main_position: args.stack_main_default_main_position,
+                };
+            }
+        }
 
         Self {
-            socket_path,
-            on_exit,
+            listen,
+            started: Instant::now(),
+            events_socket_path,
+            reconcile_interval: args.reconcile_interval_secs.map(Duration::from_secs),
+            client_idle_timeout: args.client_idle_timeout_secs.map(Duration::from_secs),
+            on_exit: args.on_exit.take(),
             message_handler: None,
-            init_args: Some((
-                final_layout,
-                workspace_renaming,
-                on_window_focus,
-                on_window_focus_leave,
-            )),
+            init_args: Some(args),
             #[cfg(feature = "wallpaper")]
             wallpaper_handles: HashMap::new(),
         }
     }
 
-    /// Handle Unix signals and run the `on_exit` command when triggered.
+    /// Refuse to start without a Sway (or i3) to talk to, with a message saying so
+    /// rather than the error of whatever first needs a connection.
+    ///
+    /// Without `SWAYSOCK` and `I3SOCK` the connection may still find the socket
+    /// through `sway --get-socketpath`, so they are only mentioned when it fails.
+    async fn ensure_sway_running() -> Result<()> {
+        const NO_SWAY: &str = "no Sway/i3 IPC socket found; is Sway running?";
+        let unset = std::env::var_os("SWAYSOCK").is_none() && std::env::var_os("I3SOCK").is_none();
+        match Connection::new().await {
+            Ok(_) => Ok(()),
+            Err(e) if unset => Err(anyhow!("{NO_SWAY} (SWAYSOCK and I3SOCK are not set: {e})")),
+            Err(e) => Err(anyhow!("{NO_SWAY} ({e})")),
+        }
+    }
+
+    /// Refuse to start when another daemon is listening on `addr`.
+    ///
+    /// A socket file nobody accepts connections on is stale and gets replaced
+    /// later, but stealing a live one would leave two daemons fighting over Sway.
+    async fn ensure_socket_unused(addr: &ListenAddr) -> Result<()> {
+        if addr.connect().await.is_ok() {
+            log::error!("Another persway daemon is already listening on {addr}");
+            bail!("another persway daemon is already listening on {addr}, not starting");
+        }
+        Ok(())
+    }
+
+    /// The socket files to remove on exit.
+    fn socket_files(&self) -> Vec<String> {
+        let mut files = vec![self.events_socket_path.clone()];
+        if let ListenAddr::Unix(path) = &self.listen {
+            files.push(path.clone());
+        }
+        files
+    }
+
+    /// Remove a stale socket file if present; ignore `NotFound`.
+    async fn remove_stale_socket(path: &str) {
+        match tokio::fs::remove_file(path).await {
+            Ok(()) => log::debug!("Removed stale socket {path}"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => log::error!("Unable to remove stale socket: {e}"),
+        }
+    }
+
+    /// Tell events subscribers persway is shutting down, run the `on_exit` command via
+    /// Sway IPC, remove the `sockets` and exit.
+    ///
+    /// Subscribers only get `SHUTDOWN_EVENT_GRACE` to receive the event, a slow one
+    /// never holds up the shutdown.
+    async fn shutdown(on_exit: Option<String>, sockets: Vec<String>, events: &EventSender) -> ! {
+        if events.receiver_count() > 0 {
+            events::publish(events, PerswayEvent::Shutdown);
+            tokio::time::sleep(SHUTDOWN_EVENT_GRACE).await;
+        }
+        if let Ok(mut commands) = Connection::new().await
+            && let Some(exit_cmd) = on_exit
+        {
+            let _ = commands.run_command(exit_cmd).await;
+        }
+        for socket in sockets {
+            let _ = std::fs::remove_file(socket);
+        }
+        exit(0);
+    }
+
+    /// Handle Unix signals and shut down when triggered.
     ///
-    /// Waits for the first of `SIGHUP`, `SIGINT`, `SIGQUIT`, or `SIGTERM`,
-    /// then runs the configured `on_exit` command via Sway IPC before exiting.
-    async fn handle_signals(mut signals: Signals, on_exit: Option<String>) {
-        if let Some(_signal) = signals.next().await {
-            if let Ok(mut commands) = Connection::new().await
-                && let Some(exit_cmd) = on_exit
+    /// `SIGHUP` reloads the workspace icons file when one is configured. Otherwise
+    /// waits for the first of `SIGHUP`, `SIGINT`, `SIGQUIT`, or `SIGTERM`,
+    /// then publishes the shutdown event, runs the configured `on_exit` command
+    /// via Sway IPC, removes the `sockets` and exits.
+    async fn handle_signals(
+        mut signals: Signals,
+        on_exit: Option<String>,
+        renamer_config: Option<Arc<RenamerConfig>>,
+        sockets: Vec<String>,
+        events: EventSender,
+    ) {
+        while let Some(signal) = signals.next().await {
+            if signal == SIGHUP
+                && let Some(config) = &renamer_config
             {
-                let _ = commands.run_command(exit_cmd).await;
+                let mut icons = config.icons.write().unwrap_or_else(PoisonError::into_inner);
+                if icons.has_file() {
+                    if let Err(e) = icons.reload() {
+                        log::error!("Keeping previous workspace icons: {e:#}");
+                    }
+                    continue;
+                }
             }
-            exit(0);
+            Self::shutdown(on_exit, sockets, &events).await;
         }
     }
 
@@ -162,19 +324,75 @@ impl Daemon {
         Ok(())
     }
 
+    /// Build the multi-line report returned by `persway status`.
+    fn status(&self) -> anyhow::Result<String> {
+        let Some(handler) = &self.message_handler else {
+            return Err(CommandError::DaemonNotInitialized.into());
+        };
+        let mut lines = vec![
+            format!("uptime: {}", format_uptime(self.started.elapsed())),
+            format!("socket: {}", self.listen),
+        ];
+        lines.extend(handler.status());
+        Ok(lines.join("\n"))
+    }
+
+    /// Reply to `persway dump-config`: the settings the daemon runs with.
+    fn dump_config(&self) -> anyhow::Result<String> {
+        let Some(handler) = &self.message_handler else {
+            return Err(CommandError::DaemonNotInitialized.into());
+        };
+        let files = crate::config::files()
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        let secs = |interval: Option<Duration>| {
+            interval.map_or_else(|| String::from("none"), |d| format!("{}s", d.as_secs()))
+        };
+        let mut lines = vec![
+            format!(
+                "config files: {}",
+                if files.is_empty() {
+                    String::from("none")
+                } else {
+                    files.join(", ")
+                }
+            ),
+            format!("socket: {}", self.listen),
+            format!("events socket: {}", self.events_socket_path),
+            format!("on exit: {}", self.on_exit.as_deref().unwrap_or("none")),
+            format!("reconcile interval: {}", secs(self.reconcile_interval)),
+            format!("client idle timeout: {}", secs(self.client_idle_timeout)),
+        ];
+        lines.extend(handler.dump_config());
+        Ok(lines.join("\n"))
+    }
+
     /// Dispatch a single CLI command and return its results.
-    async fn handle_command(&mut self, command: PerswayCommand) -> anyhow::Result<()> {
+    async fn handle_command(&mut self, command: PerswayCommand) -> Reply {
         match command {
             #[cfg(feature = "wallpaper")]
             PerswayCommand::SetWallpaper { path, output } => {
-                self.handle_set_wallpaper(path, output).await
+                self.handle_set_wallpaper(path, output).await.map(|()| None)
+            }
+            PerswayCommand::Status => self.status().map(Some),
+            PerswayCommand::DumpConfig => self.dump_config().map(Some),
+            PerswayCommand::Ping => Ok(None),
+            PerswayCommand::SetLogLevel { level } => {
+                crate::logging::set_level(level);
+                log::info!("log level set to {level}");
+                Ok(Some(format!(
+                    "log level: {}",
+                    level.as_str().to_ascii_lowercase()
+                )))
             }
+            PerswayCommand::Quit => unreachable!(),
             command => {
                 if let Some(handler) = &mut self.message_handler {
                     log::debug!("Executing CLI command: {command:?}");
                     handler.handle_command(command).await
                 } else {
-                    Err(anyhow::anyhow!("daemon not initialized"))
+                    Err(CommandError::DaemonNotInitialized.into())
                 }
             }
         }
@@ -185,46 +403,65 @@ impl Daemon {
     /// This async method:
     /// - Initializes the `MessageHandler`.
     /// - Sets up signal handling.
-    /// - Binds a Unix socket and spawns an acceptor task.
-    /// - Subscribes to Sway `Window` and `Workspace` events.
+    /// - Binds the control socket (a Unix socket unless `--listen` says otherwise) and
+    ///   spawns an acceptor task.
+    /// - Binds the events socket and streams state changes to its subscribers.
+    /// - Subscribes to Sway `Window`, `Workspace`, `Output` and `Binding` events.
     /// - Runs a `select!` loop that dispatches:
-    ///   - Sway events to `message_handler.handle_event`.
+    ///   - Sway events to `message_handler.handle_event`, reconnecting when the
+    ///     event stream ends or keeps failing.
     ///   - New socket connections to `connection_loop`.
     ///   - CLI commands to `message_handler.handle_command`.
-    ///   - Per‑connection loop that reads a single line command from a Unix socket.
+    ///   - Per‑connection loop that reads line commands from a Unix socket.
     pub async fn run(&mut self) -> Result<()> {
+        Self::ensure_sway_running().await?;
+        // Check before subscribing to Sway so a second daemon never acts on events.
+        Self::ensure_socket_unused(&self.listen).await?;
+
+        let (events_tx, _) = broadcast::channel(EVENTS_CAPACITY);
+
         // Initialize MessageHandler asynchronously (it needs a connection)
-        if let Some((layout, renaming, focus, leave)) = self.init_args.take() {
-            self.message_handler = Some(MessageHandler::new(layout, renaming, focus, leave).await?);
+        if let Some(args) = self.init_args.take() {
+            self.message_handler = Some(MessageHandler::new(args, events_tx.clone()).await?);
         }
 
         let signals = Signals::new([SIGHUP, SIGINT, SIGQUIT, SIGTERM])?;
-        tokio::spawn(Self::handle_signals(signals, self.on_exit.clone()));
+        let renamer_config = self
+            .message_handler
+            .as_ref()
+            .map(MessageHandler::renamer_config);
+        tokio::spawn(Self::handle_signals(
+            signals,
+            self.on_exit.clone(),
+            renamer_config,
+            self.socket_files(),
+            events_tx.clone(),
+        ));
 
-        // Subscribe to Window AND Workspace events
-        let subs = [EventType::Window, EventType::Workspace];
-        let mut sway_events = Connection::new().await?.subscribe(&subs).await?.fuse();
+        let mut sway_events = Self::subscribe().await?;
+        let mut event_errors = 0;
 
-        // Remove stale socket if present; ignore `NotFound`.
-        match tokio::fs::remove_file(&self.socket_path).await {
-            Ok(()) => log::debug!("Removed stale socket {}", self.socket_path),
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
-            Err(e) => log::error!("Unable to remove stale socket: {e}"),
+        if let ListenAddr::Unix(path) = &self.listen {
+            Self::remove_stale_socket(path).await;
         }
+        let listener = ControlListener::bind(&self.listen).await?;
 
-        let listener = UnixListener::bind(&self.socket_path)?;
+        Self::remove_stale_socket(&self.events_socket_path).await;
+        let events_listener = UnixListener::bind(&self.events_socket_path)?;
+        tokio::spawn(Self::events_acceptor(events_listener, events_tx.clone()));
 
         // Channel for CLI commands only
         let (sender, receiver) = mpsc::unbounded();
         let mut receiver = receiver.fuse();
         let (incoming_tx, incoming_rx) = mpsc::unbounded();
         let mut incoming_rx = incoming_rx.fuse();
+        let mut output_settle: Option<tokio::task::JoinHandle<()>> = None;
 
         // Socket Acceptor Task
         tokio::spawn(async move {
             loop {
                 match listener.accept().await {
-                    Ok((stream, _)) => {
+                    Ok(stream) => {
                         if incoming_tx.unbounded_send(stream).is_err() {
                             break;
                         }
@@ -234,99 +471,349 @@ impl Daemon {
             }
         });
 
+        if let Some(interval) = self.reconcile_interval {
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(interval);
+                // The first tick completes right away, workspaces are fresh at start.
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    if sender.unbounded_send(Message::Reconcile).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
         log::info!("Persway daemon started");
 
         loop {
             select! {
                 // 1. Sway IPC events (low latency)
-                event = sway_events.select_next_some() => match event {
-                    Ok(Event::Window(event)) => {
-                        if let Some(handler) = &mut self.message_handler
-                            && let Err(e) = handler.handle_event(event).await
-                        {
-                            log::error!("Error handling window event: {e}");
+                event = sway_events.next() => match event {
+                    Some(Ok(event)) => {
+                        event_errors = 0;
+                        self.handle_sway_event(event, &mut output_settle, &sender).await;
+                    }
+                    Some(Err(e)) => {
+                        log::error!("Sway IPC event error: {e}");
+                        event_errors += 1;
+                        if event_errors >= MAX_EVENT_ERRORS {
+                            sway_events = self.reconnect().await;
+                            event_errors = 0;
                         }
                     }
-                    Err(e) => log::error!("Sway IPC event error: {e}"),
-                    _ => {}
+                    None => {
+                        log::error!("Sway IPC event stream ended");
+                        sway_events = self.reconnect().await;
+                        event_errors = 0;
+                    }
                 },
 
                 // 2. New socket connections
                 stream = incoming_rx.select_next_some() => {
                     let sender = sender.clone();
+                    let idle_timeout = self.client_idle_timeout;
                     tokio::spawn(async move {
-                        if let Err(e) = Self::connection_loop(stream, sender).await {
+                        if let Err(e) = Self::connection_loop(stream, sender, idle_timeout).await {
                             log::error!("Connection loop error: {e}");
                         }
                     });
                 },
 
-                // 3. CLI commands
-                message = receiver.select_next_some() => {
-                    let Message::CommandEvent(command, reply_tx) = message;
-                    let res = self.handle_command(command).await;
-                    let _ = reply_tx.send(res);
+                // 3. CLI commands and deferred work
+                message = receiver.select_next_some() => match message {
+                    Message::CommandEvent(command, reply_tx) => {
+                        let res = self.handle_command(command).await;
+                        let _ = reply_tx.send(res);
+                    }
+                    Message::Quit => {
+                        log::info!("Persway daemon quitting");
+                        Self::shutdown(self.on_exit.clone(), self.socket_files(), &events_tx).await;
+                    }
+                    Message::Reconcile => {
+                        if let Some(handler) = &mut self.message_handler
+                            && let Err(e) = handler.reconcile().await
+                        {
+                            log::error!("Error reconciling layouts: {e}");
+                        }
+                    }
+                    Message::OutputsSettled => {
+                        if let Some(handler) = &mut self.message_handler
+                            && let Err(e) = handler.handle_output_event().await
+                        {
+                            log::error!("Error handling output event: {e}");
+                        }
+                    }
                 },
             }
         }
     }
+
+    /// Subscribe to the Sway `Window`, `Workspace`, `Output` and `Binding` events.
+    async fn subscribe() -> Result<Fuse<EventStream>> {
+        let subs = [
+            EventType::Window,
+            EventType::Workspace,
+            EventType::Output,
+            EventType::Binding,
+        ];
+        Ok(Connection::new().await?.subscribe(&subs).await?.fuse())
+    }
+
+    /// Subscribe to Sway events again and give the `MessageHandler` fresh connections,
+    /// after the event stream ended or kept failing (eg. sway restarted).
     ///
-    /// Parses the command via `clap::Parser` on `Args`, then sends the resulting
-    /// `PerswayCommand` over `sender` as a `Message::CommandEvent`.
+    /// Retries until it succeeds, doubling the wait between attempts up to
+    /// `RECONNECT_MAX_DELAY`. Commands wait for it to finish.
+    async fn reconnect(&mut self) -> Fuse<EventStream> {
+        let mut delay = RECONNECT_DELAY;
+        loop {
+            log::warn!("Lost the connection to sway, reconnecting in {delay:?}");
+            tokio::time::sleep(delay).await;
+            match self.try_reconnect().await {
+                Ok(events) => {
+                    log::info!("Reconnected to sway");
+                    return events;
+                }
+                Err(e) => log::error!("Failed to reconnect to sway: {e}"),
+            }
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+    }
+
+    /// A single attempt of `reconnect`.
+    async fn try_reconnect(&mut self) -> Result<Fuse<EventStream>> {
+        let events = Self::subscribe().await?;
+        if let Some(handler) = &mut self.message_handler {
+            handler.reconnect().await?;
+        }
+        Ok(events)
+    }
+
+    /// Dispatch a single Sway event to the `MessageHandler`.
+    ///
+    /// Output events are only acted on once outputs were quiet for `OUTPUT_SETTLE_DELAY`.
+    async fn handle_sway_event(
+        &mut self,
+        event: Event,
+        output_settle: &mut Option<tokio::task::JoinHandle<()>>,
+        sender: &Sender<Message>,
+    ) {
+        match event {
+            Event::Window(event) => {
+                if let Some(handler) = &mut self.message_handler
+                    && let Err(e) = handler.handle_event(event).await
+                {
+                    log::error!("Error handling window event: {e}");
+                }
+            }
+            Event::Binding(event) => {
+                if let Some(handler) = &mut self.message_handler {
+                    handler.handle_binding_event(&event);
+                }
+            }
+            Event::Output(_) => {
+                // Wait for the outputs to settle, restarting the wait on every event.
+                if let Some(handle) = output_settle.take() {
+                    handle.abort();
+                }
+                let sender = sender.clone();
+                *output_settle = Some(tokio::spawn(async move {
+                    tokio::time::sleep(OUTPUT_SETTLE_DELAY).await;
+                    let _ = sender.unbounded_send(Message::OutputsSettled);
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    /// Accept subscribers on the events socket, giving each its own writer task.
+    async fn events_acceptor(listener: UnixListener, events: EventSender) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(Self::events_loop(stream, events.subscribe()));
+                }
+                Err(e) => log::error!("Events accept error: {e}"),
+            }
+        }
+    }
+
+    /// Write every broadcast event to a single subscriber as a line of JSON.
+    ///
+    /// A subscriber that falls too far behind skips the events it missed rather
+    /// than holding up the daemon. The loop ends when the subscriber goes away.
+    async fn events_loop(mut stream: UnixStream, mut events: broadcast::Receiver<PerswayEvent>) {
+        use tokio::io::AsyncWriteExt;
+
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let line = format!("{}\n", event.to_json());
+                    if let Err(e) = stream.write_all(line.as_bytes()).await {
+                        log::debug!("Events subscriber disconnected: {e}");
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    log::warn!("Events subscriber too slow, skipped {missed} events");
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Per‑connection loop that reads line commands from the control socket until EOF.
+    ///
+    /// Each line is parsed via `clap::Parser` on `Args` and the resulting
+    /// `PerswayCommand` sent over `sender` as a `Message::CommandEvent`. Commands of
+    /// one connection run in order, each getting its reply before the next is read.
     ///
     /// # Behavior
     /// - On readable line: splits into `Vec<&str>`, parses as `Args`, sends command.
+    ///   Blank lines are skipped.
+    /// - On success: writes any data payload as `data: <line>` lines, then `success`.
+    /// - On failure: writes the error's first line as `fail:<CODE>: <line>`, preceded
+    ///   by any further lines of it as `data: <line>` lines. `CODE` comes from
+    ///   `CommandError::code_of`.
+    /// - On `quit`: writes `success`, then asks the main loop to shut down.
     /// - On EOF (0 bytes): returns `Ok(())` (connection closed).
-    /// - On invalid command: logs an error and sends `fail: invalid command`.
+    /// - On no line within `idle_timeout`: logs and returns `Ok(())`, closing the connection.
+    /// - On invalid command: logs an error and sends `fail:INVALID_COMMAND: invalid command` with
+    ///   clap's explanation as data lines, then reads the next line.
     /// - On read/write error: logs an error (no return; caller exits).
-    async fn connection_loop(stream: UnixStream, mut sender: Sender<Message>) -> Result<()> {
-        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    ///
+    /// The status line always comes last, so a reply is everything up to and
+    /// including the first line that doesn't start with `data:`.
+    async fn connection_loop(
+        stream: Box<dyn ControlStream>,
+        mut sender: Sender<Message>,
+        idle_timeout: Option<Duration>,
+    ) -> Result<()> {
+        use tokio::io::{AsyncBufReadExt, BufReader};
 
-        let (reader, mut writer) = stream.into_split();
+        let (reader, mut writer) = tokio::io::split(stream);
         let mut reader = BufReader::new(reader);
         let mut line = String::new();
 
-        match reader.read_line(&mut line).await {
-            Ok(0) => return Ok(()), // EOF
-            Ok(_) => {
-                let mut argv = line.trim().split_ascii_whitespace().collect::<Vec<_>>();
-                if let Some(first) = argv.first_mut() {
-                    *first = "persway";
-                }
-
-                match Args::try_parse_from(argv) {
-                    Ok(myargs) => {
-                        let (reply_tx, reply_rx) = oneshot::channel::<anyhow::Result<()>>();
-
-                        if sender
-                            .send(Message::CommandEvent(myargs.command, reply_tx))
-                            .await
-                            .is_err()
-                        {
-                            writer.write_all(b"fail: daemon unavailable\n").await?;
-                            return Ok(());
-                        }
-
-                        match reply_rx.await {
-                            Ok(Ok(())) => writer.write_all(b"success\n").await?,
-                            Ok(Err(e)) => {
-                                writer.write_all(format!("fail: {e}\n").as_bytes()).await?;
-                            }
-                            Err(_) => writer.write_all(b"fail: daemon dropped response\n").await?,
-                        }
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line);
+            let read = match idle_timeout {
+                Some(timeout) => {
+                    if let Ok(read) = tokio::time::timeout(timeout, read).await {
+                        read
+                    } else {
+                        log::debug!("Closing connection idle for {}s", timeout.as_secs());
+                        return Ok(());
                     }
-                    Err(e) => {
-                        // Optional: include clap's error text
-                        log::error!("Invalid command: {e}");
-                        writer.write_all(b"fail: invalid command\n").await?;
+                }
+                None => read.await,
+            };
+            match read {
+                Ok(0) => return Ok(()), // EOF
+                Ok(_) if line.trim().is_empty() => {}
+                Ok(_) => {
+                    if !Self::handle_line(&line, &mut sender, &mut writer).await? {
+                        return Ok(());
                     }
                 }
+                Err(e) => {
+                    log::error!("Socket read error: {e}");
+                    return Ok(());
+                }
             }
+        }
+    }
+
+    /// Run the command on `line` and write its reply.
+    ///
+    /// Returns `false` when the daemon no longer takes commands, so the
+    /// connection should be closed.
+    async fn handle_line(
+        line: &str,
+        sender: &mut Sender<Message>,
+        writer: &mut ControlWriter,
+    ) -> Result<bool> {
+        let mut argv = line.trim().split_ascii_whitespace().collect::<Vec<_>>();
+        if let Some(first) = argv.first_mut() {
+            *first = "persway";
+        }
+
+        let myargs = match Args::try_parse_from(argv) {
+            Ok(myargs) => myargs,
             Err(e) => {
-                log::error!("Socket read error: {e}");
+                log::error!("Invalid command: {e}");
+                Self::write_reply(
+                    writer,
+                    &e.to_string(),
+                    "fail:INVALID_COMMAND: invalid command",
+                )
+                .await?;
+                return Ok(true);
+            }
+        };
+
+        // Answer before asking the main loop to exit, so the client gets its reply.
+        if let PerswayCommand::Quit = myargs.command {
+            Self::write_reply(writer, "", "success").await?;
+            let _ = sender.send(Message::Quit).await;
+            return Ok(false);
+        }
+
+        let (reply_tx, reply_rx) = oneshot::channel::<Reply>();
+        if sender
+            .send(Message::CommandEvent(myargs.command, reply_tx))
+            .await
+            .is_err()
+        {
+            Self::write_reply(writer, "", "fail:DAEMON_UNAVAILABLE: daemon unavailable").await?;
+            return Ok(false);
+        }
+
+        match reply_rx.await {
+            Ok(Ok(data)) => {
+                Self::write_reply(writer, &data.unwrap_or_default(), "success").await?;
+            }
+            Ok(Err(e)) => {
+                let msg = e.to_string();
+                let (summary, details) = msg.split_once('\n').unwrap_or((&msg, ""));
+                let code = CommandError::code_of(&e);
+                Self::write_reply(writer, details, &format!("fail:{code}: {summary}")).await?;
+            }
+            Err(_) => {
+                Self::write_reply(
+                    writer,
+                    "",
+                    "fail:DAEMON_UNAVAILABLE: daemon dropped response",
+                )
+                .await?;
             }
         }
+        Ok(true)
+    }
 
+    /// Write a reply: each line of `body` as a `data: <line>` line, then the `status` line.
+    async fn write_reply(writer: &mut ControlWriter, body: &str, status: &str) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut reply = String::new();
+        for line in body.lines() {
+            reply.push_str("data: ");
+            reply.push_str(line);
+            reply.push('\n');
+        }
+        reply.push_str(status);
+        reply.push('\n');
+        writer.write_all(reply.as_bytes()).await?;
         Ok(())
     }
 }
+
+/// Format an uptime as eg. `2h 05m 09s`.
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    format!("{}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+}
@@ -1,2 +1,4 @@
+pub mod centered_main;
+pub mod dwindle;
 pub mod spiral;
 pub mod stack_main;
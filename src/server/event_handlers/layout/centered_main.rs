@@ -0,0 +1,201 @@
+//! Centered‑main layout manager for Persway.
+//!
+//! Implements a three‑region layout:
+//! - A “main” window in the middle with a fixed relative width.
+//! - A left and a right stack holding the remaining windows, filled alternately.
+//!
+//! With fewer than three windows the workspace is left as plain tiling.
+
+use crate::{
+    node_ext::NodeExt,
+    utils::{is_special_workspace_node, run_command, tree_or_fetch},
+};
+
+use anyhow::Result;
+use std::fmt::Write;
+use std::sync::Arc;
+use swayipc_async::{Connection, Node, WindowChange, WindowEvent};
+
+use super::super::traits::WindowEventHandler;
+
+/// Centered‑main layout manager.
+///
+/// Maintains a top level of `[left stack, main, right stack]` where both stacks
+/// are vertical splits and main keeps `size` percent of the width.
+pub struct CenteredMain {
+    /// Connection to Sway IPC used for querying the tree and running commands.
+    connection: Connection,
+    /// Relative size of the main area as a percentage (0–100).
+    size: u8,
+    /// `app_id`/class patterns of windows that are left alone like floating windows.
+    layout_ignore: Vec<String>,
+    /// Only log the commands instead of running them.
+    dry_run: bool,
+}
+
+/// Number of windows inside `node` (1 for a window itself).
+fn window_count(node: &Node) -> usize {
+    node.iter().filter(|n| n.is_window()).count()
+}
+
+impl CenteredMain {
+    /// Entry point for a centered‑main layout pass.
+    ///
+    /// # Arguments
+    /// - `event`: The event to process (wrapped in `Box`).
+    /// - `tree`: The tree fetched for this event pass, if any.
+    /// - `size`: Main area size in percent.
+    /// - `layout_ignore`: `app_id`/class patterns of windows that are never tiled.
+    /// - `dry_run`: Only log the commands instead of running them.
+    pub async fn handle(
+        event: Box<WindowEvent>,
+        tree: Option<Arc<Node>>,
+        size: u8,
+        layout_ignore: Vec<String>,
+        dry_run: bool,
+    ) {
+        if let Ok(mut manager) = Self::new(size, layout_ignore, dry_run).await {
+            manager.handle(event, tree).await;
+        }
+    }
+
+    /// Create a new `CenteredMain` instance.
+    pub async fn new(size: u8, layout_ignore: Vec<String>, dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            size,
+            layout_ignore,
+            dry_run,
+        })
+    }
+
+    /// Handle a `WindowChange::New` event for centered‑main layout.
+    ///
+    /// - Fewer than 3 windows: nothing to do, the first window simply is main.
+    /// - Exactly 3 top‑level windows: center the oldest one as main and turn the
+    ///   other two into the left and right stacks.
+    /// - Otherwise: move the new window into whichever stack holds fewer windows.
+    async fn on_new_window(&mut self, event: &WindowEvent, tree: &Node) -> Result<()> {
+        let Some(node) = tree.find_as_ref(|n| n.id == event.container.id) else {
+            log::warn!(
+                "centered_main: no node found with id {}",
+                event.container.id
+            );
+            return Ok(());
+        };
+        let Some(wstree) = node.find_workspace_in(tree) else {
+            return Ok(());
+        };
+        if is_special_workspace_node(wstree) {
+            log::debug!("skip centered_main layout of \"special\" workspace");
+            return Ok(());
+        }
+
+        if node.is_floating() || node.is_full_screen() || node.matches_app(&self.layout_ignore) {
+            log::debug!("skip centered_main layout of floating, fullscreen or ignored window");
+            return Ok(());
+        }
+
+        let new_id = event.container.id;
+        let windows = window_count(wstree);
+        log::debug!("centered_main new_window id: {new_id}, windows: {windows}");
+        if windows < 3 {
+            return Ok(());
+        }
+
+        let cmd = if wstree.nodes.len() == 3 && wstree.nodes.iter().all(NodeExt::is_window) {
+            self.build_layout(wstree, new_id)
+        } else {
+            Self::place_in_stack(wstree, new_id)
+        };
+
+        if let Some(cmd) = cmd {
+            log::debug!("centered_main new_window: {cmd}");
+            run_command(&mut self.connection, cmd, self.dry_run).await?;
+        }
+        Ok(())
+    }
+
+    /// Build the three regions out of three top‑level windows.
+    fn build_layout(&self, wstree: &Node, new_id: i64) -> Option<String> {
+        let main = wstree
+            .nodes
+            .iter()
+            .filter(|n| n.id != new_id)
+            .min_by_key(|n| n.id)?;
+        let middle = &wstree.nodes[1];
+        let mut cmd = String::new();
+        if middle.id != main.id {
+            let _ = write!(
+                cmd,
+                "[con_id={}] swap container with con_id {}; ",
+                main.id, middle.id
+            );
+        }
+        for side in wstree.nodes.iter().filter(|n| n.id != main.id) {
+            let _ = write!(cmd, "[con_id={}] split v; ", side.id);
+        }
+        let _ = write!(
+            cmd,
+            "[con_id={}] resize set width {}; [con_id={new_id}] focus",
+            main.id, self.size
+        );
+        Some(cmd)
+    }
+
+    /// Move the new window into the stack with fewer windows, left on a tie.
+    fn place_in_stack(wstree: &Node, new_id: i64) -> Option<String> {
+        let stacks = wstree
+            .nodes
+            .iter()
+            .filter(|n| !n.is_window())
+            .collect::<Vec<_>>();
+        let [left, right] = stacks.as_slice() else {
+            log::debug!("centered_main: workspace doesn't have two stacks, leaving it alone");
+            return None;
+        };
+        let others =
+            |stack: &Node| window_count(stack) - usize::from(stack.iter().any(|n| n.id == new_id));
+        let target = if others(left) <= others(right) {
+            left
+        } else {
+            right
+        };
+        if target.iter().any(|n| n.id == new_id) {
+            return None;
+        }
+        let mark = format!("_stack_{}", target.id);
+        Some(format!(
+            "[con_id={}] mark --add {mark}; [con_id={new_id}] focus; move container to mark {mark}; [con_mark={mark}] unmark {mark}; [con_id={new_id}] focus",
+            target.id
+        ))
+    }
+}
+
+impl WindowEventHandler for CenteredMain {
+    /// The tree is only needed for the events this manager acts on.
+    fn needs_tree(change: WindowChange) -> bool {
+        matches!(change, WindowChange::New | WindowChange::Move)
+    }
+
+    /// Handle a `WindowEvent` in the centered‑main layout manager.
+    ///
+    /// `WindowChange::New` and `Move` (eg. windows moved back in by a relayout)
+    /// trigger layout work; others are logged and ignored.
+    async fn handle(&mut self, event: Box<WindowEvent>, tree: Option<Arc<Node>>) {
+        match event.change {
+            WindowChange::New | WindowChange::Move => {
+                log::debug!("centered_main handler handling event: {:?}", event.change);
+                let res = match tree_or_fetch(&mut self.connection, tree).await {
+                    Ok(tree) => self.on_new_window(&event, &tree).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = res {
+                    log::error!("centered_main layout err: {e}");
+                }
+            }
+            _ => log::debug!("centered_main not handling event: {:?}", event.change),
+        }
+    }
+}
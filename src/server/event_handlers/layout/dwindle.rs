@@ -0,0 +1,186 @@
+//! Dwindle layout manager for Persway.
+//!
+//! Handles:
+//! - A background task that serially processes `WindowEvent`s.
+//! - Binary-tree (BSP) tiling where every new window halves the space of the
+//!   window it was opened next to.
+//! - Split decisions based on the parent container's geometry so the tree stays balanced.
+
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::super::traits::{EventTreeSender, WindowEventHandler};
+use crate::{
+    node_ext::NodeExt,
+    utils::{get_focused_workspace, is_special_workspace_node, run_command, tree_or_fetch},
+};
+
+use anyhow::Result;
+use swayipc_async::{Connection, Node, NodeLayout, WindowChange, WindowEvent};
+
+/// Dwindle layout manager.
+///
+/// Runs in a background task and:
+/// - Receives `WindowEvent`s via `dwindle_tx`.
+/// - On `New`/`Move`/`Close`, prepares the relevant window so the next window splits it
+///   in the opposite direction of its parent.
+/// - Ignores focus changes, so moving focus around never reflows the tree.
+pub struct Dwindle {
+    /// Connection to Sway used for querying the tree and running commands.
+    connection: Connection,
+    /// `app_id`/class patterns of windows that are never split.
+    layout_ignore: Vec<String>,
+    /// Only log the commands instead of running them.
+    dry_run: bool,
+}
+
+impl Dwindle {
+    /// Spawn a background task that sequentially handles dwindle layout events.
+    ///
+    /// # Arguments
+    /// - `layout_ignore`: `app_id`/class patterns of windows that should never be split.
+    /// - `dry_run`: Only log the commands instead of running them.
+    ///
+    /// # Return
+    /// `EventTreeSender` for forwarding events to dwindle.
+    pub fn spawn_handler(layout_ignore: Vec<String>, dry_run: bool) -> EventTreeSender {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            match Self::new(layout_ignore, dry_run).await {
+                Ok(mut manager) => {
+                    log::debug!("dwindle manager: handler task started");
+                    while let Some((event, tree)) = rx.recv().await {
+                        manager.handle(event, tree).await;
+                    }
+                    log::debug!("dwindle manager: handler task stopped");
+                }
+                Err(e) => {
+                    log::error!("dwindle manager: failed to initialize: {e}");
+                }
+            }
+        });
+
+        tx
+    }
+
+    /// Create a new `Dwindle` instance.
+    async fn new(layout_ignore: Vec<String>, dry_run: bool) -> Result<Self> {
+        let connection = Connection::new().await?;
+        Ok(Self {
+            connection,
+            layout_ignore,
+            dry_run,
+        })
+    }
+
+    /// Set the split of window `id` so the next window dwindles into it.
+    ///
+    /// The split is the opposite of the parent's longer edge: a window inside a
+    /// wide parent is split vertically, one inside a tall parent horizontally.
+    async fn split_window(&mut self, id: i64, tree: &Node) -> Result<()> {
+        let Some(node) = tree.find_as_ref(|n| n.id == id) else {
+            log::debug!("dwindle layout: node {id} no longer exists (stale event), skipping");
+            return Ok(());
+        };
+
+        let Some(ws) = node.find_workspace_in(tree) else {
+            log::debug!("dwindle layout: couldn't get workspace for node {id}, skipping");
+            return Ok(());
+        };
+
+        if is_special_workspace_node(ws) {
+            log::debug!("skip dwindle layout of \"special\" workspace");
+            return Ok(());
+        }
+
+        if node.is_floating() || node.is_full_screen() || node.matches_app(&self.layout_ignore) {
+            log::debug!("skip dwindle layout of floating, fullscreen or ignored window {id}");
+            return Ok(());
+        }
+
+        let Some(parent) = node.find_parent_in(tree) else {
+            log::debug!("dwindle layout: no parent found for node {id}, skipping");
+            return Ok(());
+        };
+
+        if matches!(parent.layout, NodeLayout::Tabbed | NodeLayout::Stacked) {
+            log::debug!("skip dwindle layout of tabbed/stacked container");
+            return Ok(());
+        }
+
+        let desired_layout = dwindle_split(parent);
+        if node.layout == desired_layout {
+            log::debug!("dwindle layout: node {id} already has correct split, skipping");
+            return Ok(());
+        }
+
+        let cmd = match desired_layout {
+            NodeLayout::SplitV => format!("[con_id={id}] split v"),
+            _ => format!("[con_id={id}] split h"),
+        };
+        log::debug!("dwindle layout: applying change -> {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await?;
+        Ok(())
+    }
+
+    /// Handle a `WindowChange::New` event by preparing the new window's split.
+    async fn on_new_window(&mut self, event: &WindowEvent, tree: &Node) -> Result<()> {
+        self.split_window(event.container.id, tree).await
+    }
+
+    /// Handle a `WindowChange::Close` event.
+    ///
+    /// Closing a window hands its space to a sibling, so the focused window's
+    /// split is re-evaluated against its new parent.
+    async fn on_close_window(&mut self, tree: &Node) -> Result<()> {
+        let ws = get_focused_workspace(&mut self.connection).await?;
+        let Some(focused) = tree
+            .find_as_ref(|n| n.id == ws.id)
+            .and_then(|wstree| wstree.find_as_ref(|n| n.is_window() && n.focused))
+        else {
+            return Ok(());
+        };
+        let id = focused.id;
+        self.split_window(id, tree).await
+    }
+}
+
+/// The split a window should get given the container it lives in.
+fn dwindle_split(parent: &Node) -> NodeLayout {
+    if parent.rect.width > parent.rect.height {
+        NodeLayout::SplitV
+    } else {
+        NodeLayout::SplitH
+    }
+}
+
+impl WindowEventHandler for Dwindle {
+    /// The tree is only needed for the events this manager acts on.
+    fn needs_tree(change: WindowChange) -> bool {
+        matches!(
+            change,
+            WindowChange::New | WindowChange::Move | WindowChange::Close
+        )
+    }
+
+    /// Handle a `WindowEvent` in the dwindle layout manager.
+    ///
+    /// Only `WindowChange::New`, `Move` and `Close` trigger layout work;
+    /// focus changes are deliberately ignored.
+    async fn handle(&mut self, event: Box<WindowEvent>, tree: Option<Arc<Node>>) {
+        if !Self::needs_tree(event.change) {
+            log::debug!("dwindle manager, not handling event: {:?}", event.change);
+            return;
+        }
+        let res = match tree_or_fetch(&mut self.connection, tree).await {
+            // A window moved in (eg. by a relayout) is placed just like a new one.
+            Ok(tree) if event.change == WindowChange::Close => self.on_close_window(&tree).await,
+            Ok(tree) => self.on_new_window(&event, &tree).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            log::error!("dwindle manager, layout err: {e}");
+        }
+    }
+}
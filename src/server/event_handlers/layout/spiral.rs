@@ -2,20 +2,45 @@
 //!
 //! Handles:
 //! - A background task that serially processes `WindowEvent`s.
-//! - Dynamic layout switching (`split v` / `split h`) based on window aspect ratio.
+//! - Dynamic layout switching (`split v` / `split h`) based on window aspect ratio, with a
+//!   hysteresis band so nearly square windows don't flip back and forth.
 //! - Throttling of rapid focus events to avoid flickering.
+//! - Laying out a workspace again when one of its windows is floated or unfloated.
+//! - Leaving windows alone for a while after they were resized by hand.
+//! - Splitting in one direction only on workspaces with a forced split direction.
 
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
 use super::super::traits::WindowEventHandler;
 use crate::{
+    layout::SplitDir,
     node_ext::NodeExt,
-    utils::{is_persway_tmp_workspace, is_scratchpad_workspace},
+    utils::{is_special_workspace_node, run_command, tree_or_fetch},
 };
 
 use anyhow::Result;
-use swayipc_async::{Connection, NodeLayout, WindowChange, WindowEvent, Workspace};
+use swayipc_async::{Connection, Node, NodeLayout, WindowChange, WindowEvent};
+
+/// Sender to the spiral handler task: each event comes with the tree of its pass
+/// and the split direction forced on its workspace.
+pub type SpiralSender = mpsc::UnboundedSender<(Box<WindowEvent>, Option<Arc<Node>>, SplitDir)>;
+
+/// Settings of the spiral layout, see `--spiral-ratio`, `--spiral-throttle-ms`
+/// and `--spiral-resize-cooldown-ms`.
+#[derive(Debug, Clone, Copy)]
+pub struct SpiralSettings {
+    /// Share of the space the older window keeps on a split, `None` for equal halves.
+    pub ratio: Option<f32>,
+    /// How much longer one side has to be than the other to change a split.
+    pub hysteresis: f32,
+    /// Minimum time between two layout passes.
+    pub throttle: Duration,
+    /// How long a window whose size changed is left unsplit, `None` to never wait.
+    pub resize_cooldown: Option<Duration>,
+}
 
 /// Spiral layout manager.
 ///
@@ -31,32 +56,54 @@ pub struct Spiral {
     last_focused_id: Option<i64>,
     /// Last time a layout pass was performed, used for throttling.
     last_layout_time: Option<Instant>,
-}
-
-/// Determine whether a workspace should be skipped for spiral layout.
-///
-/// Special workspaces (e.g., temporary or scratchpad) are not laid out by spiral.
-fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
-    is_persway_tmp_workspace(workspace) || is_scratchpad_workspace(workspace)
+    /// `app_id`/class patterns of windows that are never split.
+    layout_ignore: Vec<String>,
+    /// Share of the space the older window keeps on a split, `None` for equal halves.
+    ratio: Option<f32>,
+    /// How much longer one side has to be than the other to change a split.
+    hysteresis: f32,
+    /// Minimum time between two layout passes.
+    throttle: Duration,
+    /// How long a window whose size changed is left unsplit, `None` to never wait.
+    resize_cooldown: Option<Duration>,
+    /// Size (width, height) of each window when it was last focused.
+    last_sizes: HashMap<i64, (i32, i32)>,
+    /// When the size of a window was last seen changing.
+    resized_at: HashMap<i64, Instant>,
+    /// Split direction forced on the workspace of the event being handled.
+    split_dir: SplitDir,
+    /// Only log the commands instead of running them.
+    dry_run: bool,
 }
 
 impl Spiral {
     /// Spawn a background task that sequentially handles spiral layout events.
     ///
     /// The returned `UnboundedSender` should be used to send `Box<WindowEvent>`
-    /// to the spiral manager from the `MessageHandler`.
+    /// (along with the tree of its event pass and the split direction forced on its
+    /// workspace) to the spiral manager from the `MessageHandler`.
+    ///
+    /// # Arguments
+    /// - `layout_ignore`: `app_id`/class patterns of windows that should never be split.
+    /// - `settings`: Split ratio, throttle and resize cooldown.
+    /// - `dry_run`: Only log the commands instead of running them.
     ///
     /// # Return
-    /// `mpsc::UnboundedSender<Box<WindowEvent>>` for forwarding events to spiral.
-    pub fn spawn_handler() -> mpsc::UnboundedSender<Box<WindowEvent>> {
+    /// `SpiralSender` for forwarding events to spiral.
+    pub fn spawn_handler(
+        layout_ignore: Vec<String>,
+        settings: SpiralSettings,
+        dry_run: bool,
+    ) -> SpiralSender {
         let (tx, mut rx) = mpsc::unbounded_channel();
 
         tokio::spawn(async move {
-            match Self::new().await {
+            match Self::new(layout_ignore, settings, dry_run).await {
                 Ok(mut manager) => {
                     log::debug!("spiral manager: handler task started");
-                    while let Some(event) = rx.recv().await {
-                        manager.handle(event).await;
+                    while let Some((event, tree, split_dir)) = rx.recv().await {
+                        manager.split_dir = split_dir;
+                        manager.handle(event, tree).await;
                     }
                     log::debug!("spiral manager: handler task stopped");
                 }
@@ -72,27 +119,66 @@ impl Spiral {
     /// Create a new `Spiral` instance.
     ///
     /// Connects to Sway IPC and initializes internal state.
-    async fn new() -> Result<Self> {
+    async fn new(
+        layout_ignore: Vec<String>,
+        settings: SpiralSettings,
+        dry_run: bool,
+    ) -> Result<Self> {
         let connection = Connection::new().await?;
         Ok(Self {
             connection,
             last_focused_id: None,
             last_layout_time: None,
+            layout_ignore,
+            ratio: settings.ratio,
+            hysteresis: settings.hysteresis,
+            throttle: settings.throttle,
+            resize_cooldown: settings.resize_cooldown,
+            last_sizes: HashMap::new(),
+            resized_at: HashMap::new(),
+            split_dir: SplitDir::Auto,
+            dry_run,
         })
     }
 
+    /// Record the size of `node` and tell whether it changed within the resize cooldown.
+    ///
+    /// Sway has no resize event, so a window whose size differs from when it was
+    /// last focused is taken to have been resized by hand.
+    fn recently_resized(&mut self, node: &Node) -> bool {
+        let Some(cooldown) = self.resize_cooldown else {
+            return false;
+        };
+        let size = (node.rect.width, node.rect.height);
+        if self
+            .last_sizes
+            .insert(node.id, size)
+            .is_some_and(|last| last != size)
+        {
+            self.resized_at.insert(node.id, Instant::now());
+        }
+        self.resized_at
+            .get(&node.id)
+            .is_some_and(|at| at.elapsed() < cooldown)
+    }
+
+    /// Forget what was recorded about the window `id`, it was closed.
+    fn forget(&mut self, id: i64) {
+        self.last_sizes.remove(&id);
+        self.resized_at.remove(&id);
+    }
+
     /// Perform spiral layout for a single window event.
     ///
     /// This method:
     /// - Throttles very rapid layout passes.
     /// - Skips duplicate focus events for the same container.
-    /// - Skips special workspaces (tmp, scratchpad).
-    /// - Computes whether a node should be `split v` or `split h` and applies it if needed.
-    async fn layout(&mut self, event: WindowEvent) -> Result<()> {
+    /// - Lays out the focused node with `layout_node`.
+    async fn layout(&mut self, event: WindowEvent, tree: Option<Arc<Node>>) -> Result<()> {
         log::debug!("spiral manager handling event: {:?}", event.change);
 
         if let Some(last_time) = self.last_layout_time
-            && last_time.elapsed() < Duration::from_millis(50)
+            && last_time.elapsed() < self.throttle
         {
             log::debug!("spiral layout: throttling rapid events");
             return Ok(());
@@ -110,7 +196,7 @@ impl Spiral {
         }
         self.last_focused_id = Some(event.container.id);
 
-        let tree = self.connection.get_tree().await?;
+        let tree = tree_or_fetch(&mut self.connection, tree).await?;
 
         // Handle stale node references gracefully
         let Some(node) = tree.find_as_ref(|n| n.id == event.container.id) else {
@@ -121,67 +207,171 @@ impl Spiral {
             return Ok(());
         };
 
-        let ws = match node.get_workspace().await {
-            Ok(ws) => ws,
-            Err(e) => {
-                log::debug!(
-                    "spiral layout: couldn't get workspace for node {} ({}), skipping",
-                    node.id,
-                    e
-                );
-                return Ok(());
-            }
+        self.layout_node(&tree, node, true).await
+    }
+
+    /// Lay out every tiled window of the workspace of a window that was floated or
+    /// unfloated, the space it left or took changed the shape of its neighbors.
+    ///
+    /// Not throttled, and the sizes seen here don't count as resizes by hand.
+    async fn refresh(&mut self, event: WindowEvent, tree: Option<Arc<Node>>) -> Result<()> {
+        let tree = tree_or_fetch(&mut self.connection, tree).await?;
+        let Some(ws) = tree
+            .find_as_ref(|n| n.id == event.container.id)
+            .and_then(|node| node.find_workspace_in(&tree))
+        else {
+            log::debug!(
+                "spiral layout: node {} no longer on a workspace, skipping",
+                event.container.id
+            );
+            return Ok(());
         };
+        for node in ws.iter().filter(|n| n.is_window()) {
+            self.layout_node(&tree, node, false).await?;
+        }
+        // The next focus event is a fresh start, even for the same window.
+        self.last_focused_id = None;
+        Ok(())
+    }
 
-        if should_skip_layout_of_workspace(&ws) {
+    /// Split a single node of `tree` the way its shape calls for.
+    ///
+    /// - Skips special workspaces (tmp, scratchpad), tabbed/stacked ones and ignored apps.
+    /// - With `check_resize`, skips windows resized by hand within the resize cooldown.
+    /// - Computes whether a node should be `split v` or `split h` and applies it if needed,
+    ///   keeping the split of nodes within the hysteresis band.
+    async fn layout_node(&mut self, tree: &Node, node: &Node, check_resize: bool) -> Result<()> {
+        let Some(ws) = node.find_workspace_in(tree) else {
+            log::debug!(
+                "spiral layout: couldn't get workspace for node {}, skipping",
+                node.id
+            );
+            return Ok(());
+        };
+
+        if is_special_workspace_node(ws) {
             log::debug!("skip spiral layout of \"special\" workspace");
             return Ok(());
         }
 
+        if ws.is_tabbed_or_stacked_workspace() {
+            log::debug!("skip spiral layout of tabbed/stacked workspace");
+            return Ok(());
+        }
+
+        if !check_resize {
+            self.last_sizes
+                .insert(node.id, (node.rect.width, node.rect.height));
+        } else if self.recently_resized(node) {
+            log::debug!(
+                "spiral layout: node {} was resized recently, skipping",
+                node.id
+            );
+            return Ok(());
+        }
+
+        let parent = node.find_parent_in(tree);
+        let in_stack_or_tabs =
+            parent.is_some_and(|p| matches!(p.layout, NodeLayout::Stacked | NodeLayout::Tabbed));
+
         if !(node.is_floating_window()
             || node.is_floating_container()
             || node.is_full_screen()
-            || node.is_stacked().await?
-            || node.is_tabbed().await?)
+            || node.matches_app(&self.layout_ignore)
+            || in_stack_or_tabs)
         {
-            let desired_layout = if node.rect.height > node.rect.width {
-                NodeLayout::SplitV
-            } else {
-                NodeLayout::SplitH
-            };
+            let desired_layout = self.desired_layout(node);
 
             // ONLY run the command if the current layout is different
-            if node.layout == desired_layout {
-                log::debug!(
-                    "spiral layout: node {} already has correct split, skipping",
-                    node.id
-                );
+            if desired_layout.is_none_or(|layout| node.layout == layout) {
+                log::debug!("spiral layout: node {} keeps its split, skipping", node.id);
             } else {
-                let cmd = match desired_layout {
-                    NodeLayout::SplitV => format!("[con_id={}] split v", node.id),
-                    NodeLayout::SplitH => format!("[con_id={}] split h", node.id),
+                let mut cmd = match desired_layout {
+                    Some(NodeLayout::SplitV) => format!("[con_id={}] split v", node.id),
+                    Some(NodeLayout::SplitH) => format!("[con_id={}] split h", node.id),
                     _ => unreachable!(),
                 };
+                // Resizing only together with a split leaves manual resizes alone.
+                if let Some(resize) = self.ratio_resize(node, parent) {
+                    cmd.push_str("; ");
+                    cmd.push_str(&resize);
+                }
                 log::debug!("spiral layout: applying change -> {cmd}");
-                self.connection.run_command(cmd).await?;
+                run_command(&mut self.connection, cmd, self.dry_run).await?;
             }
         }
 
         Ok(())
     }
+
+    /// The split `node` should have, `None` while neither side is `self.hysteresis`
+    /// times longer than the other and the node keeps the split it has.
+    ///
+    /// A split direction forced with `spiral-force-dir` wins over the shape of the node.
+    fn desired_layout(&self, node: &Node) -> Option<NodeLayout> {
+        match self.split_dir {
+            SplitDir::H => return Some(NodeLayout::SplitH),
+            SplitDir::V => return Some(NodeLayout::SplitV),
+            SplitDir::Auto => {}
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let (width, height) = (node.rect.width as f32, node.rect.height as f32);
+        if height > width * self.hysteresis {
+            Some(NodeLayout::SplitV)
+        } else if width > height * self.hysteresis {
+            Some(NodeLayout::SplitH)
+        } else {
+            None
+        }
+    }
+
+    /// The resize that gives the older window of a fresh split `self.ratio` of the space.
+    ///
+    /// Only applies to the newer window of a pair, ie. a window that was just
+    /// opened next to the window spiral split before.
+    fn ratio_resize(&self, node: &Node, parent: Option<&Node>) -> Option<String> {
+        let ratio = self.ratio?;
+        let parent = parent.filter(|p| p.nodes.len() == 2)?;
+        if parent.nodes.iter().any(|n| n.id > node.id) {
+            return None;
+        }
+        let dimension = match parent.layout {
+            NodeLayout::SplitH => "width",
+            NodeLayout::SplitV => "height",
+            _ => return None,
+        };
+        let share = (1.0 - ratio) * 100.0;
+        Some(format!(
+            "[con_id={}] resize set {dimension} {share:.0} ppt",
+            node.id
+        ))
+    }
 }
 
 impl WindowEventHandler for Spiral {
+    /// The tree is only needed for the events this manager acts on.
+    fn needs_tree(change: WindowChange) -> bool {
+        matches!(change, WindowChange::Focus | WindowChange::Floating)
+    }
+
     /// Handle a `WindowEvent` in the spiral layout manager.
     ///
-    /// Only `WindowChange::Focus` events trigger layout work; all others are logged and ignored.
-    async fn handle(&mut self, event: Box<WindowEvent>) {
+    /// `WindowChange::Focus` events lay out the focused window, `WindowChange::Floating`
+    /// ones its whole workspace and `WindowChange::Close` clears what was recorded about
+    /// the window; all others are logged and ignored.
+    async fn handle(&mut self, event: Box<WindowEvent>, tree: Option<Arc<Node>>) {
         match event.change {
             WindowChange::Focus => {
-                if let Err(e) = self.layout(*event).await {
+                if let Err(e) = self.layout(*event, tree).await {
                     log::error!("spiral manager, layout err: {e}");
                 }
             }
+            WindowChange::Floating => {
+                if let Err(e) = self.refresh(*event, tree).await {
+                    log::error!("spiral manager, refresh err: {e}");
+                }
+            }
+            WindowChange::Close => self.forget(event.container.id),
             _ => log::debug!("spiral manager, not handling event: {:?}", event.change),
         }
     }
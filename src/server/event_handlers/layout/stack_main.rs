@@ -1,22 +1,59 @@
 //! Stack‑main layout manager for Persway.
 //!
 //! Implements a two‑region layout:
-//! - A “main” area with a fixed relative width.
-//! - A “stack” area containing the rest of the windows, laid out as `tabbed`, `stacked`, or tiled.
+//! - A “main” area with a fixed relative width (or height, with the main area at the bottom).
+//! - A “stack” area containing the rest of the windows, laid out as `tabbed`, `stacked`, or tiled,
+//!   either on the left of the main area or as a strip along the top.
 //!
-//! Handles `new`, `close`, `move`, and `floating` window events to maintain this structure.
+//! Handles `new`, `close`, `move`, and `floating` window events to maintain this structure,
+//! one at a time in a background task so bursts of events don't race each other.
 
 use crate::{
-    layout::StackLayout,
+    layout::{
+        MainPosition, SIZE_RANGE, STACK_MAIN_DEFAULT_SIZE, StackInsert, StackLayout, resize_amount,
+    },
     node_ext::NodeExt,
-    utils::{get_focused_workspace, is_persway_tmp_workspace, is_scratchpad_workspace},
+    utils::{
+        get_focused_workspace, is_persway_tmp_workspace, is_scratchpad_workspace,
+        is_special_workspace_node, run_command, tree_or_fetch,
+    },
 };
 
 use anyhow::Result;
-use swayipc_async::{Connection, WindowChange, WindowEvent, Workspace};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use swayipc_async::{Connection, Node, NodeLayout, WindowChange, WindowEvent, Workspace};
+use tokio::sync::mpsc;
 
 use super::super::traits::WindowEventHandler;
 
+/// The same event for the same window arriving within this long is only handled once.
+const DUPLICATE_EVENT_WINDOW: Duration = Duration::from_millis(50);
+
+/// Sender to the stack‑main handler task: each event comes with the tree of its
+/// pass and the main area size, stack layout and main position of its workspace.
+pub type StackMainSender = mpsc::UnboundedSender<(
+    Box<WindowEvent>,
+    Option<Arc<Node>>,
+    u8,
+    StackLayout,
+    MainPosition,
+)>;
+
+/// Settings of the stack‑main layout, see `--stack-insert`, `--stack-main-min-main-px`
+/// and `--gap-aware-resize`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StackMainSettings {
+    /// End of the stack the window leaving main goes to, next to the last focused
+    /// stack window if `None`.
+    pub insert: Option<StackInsert>,
+    /// Smallest width (height with the main area at the bottom) of the main area
+    /// in pixels.
+    pub min_main_px: Option<u32>,
+    /// Size the main area in pixels between the inner gaps rather than in `ppt`.
+    pub gap_aware_resize: bool,
+}
+
 /// Decide whether a workspace should be skipped for stack‑main layout.
 ///
 /// “Special” workspaces (e.g., temporary or scratchpad) are not managed by stack‑main.
@@ -24,6 +61,122 @@ fn should_skip_layout_of_workspace(workspace: &Workspace) -> bool {
     is_persway_tmp_workspace(workspace) || is_scratchpad_workspace(workspace)
 }
 
+/// The command `on_new_window` runs for window `new_id` that opened on workspace `ws`,
+/// `None` if there is nothing to do.
+///
+/// The first window of a workspace is main and the second starts the stack, whichever
+/// of the two sway put first. Later windows take the main area and push the main
+/// window into the stack, at its `insert` end if set. `amount` is what the stack is
+/// resized to when it is created.
+fn new_window_cmd(
+    ws: &Node,
+    new_id: i64,
+    stack_layout: &StackLayout,
+    main_position: MainPosition,
+    insert: Option<StackInsert>,
+    amount: &str,
+) -> Option<String> {
+    // The stack splits across the main area, the workspace along it.
+    let (stack_split, workspace_split) = match main_position {
+        MainPosition::Right => ("split v", "split h"),
+        MainPosition::Bottom => ("split h", "split v"),
+    };
+    let layout = match stack_layout {
+        StackLayout::Tabbed => format!("{stack_split}; layout tabbed"),
+        StackLayout::Stacked => format!("{stack_split}; layout stacking"),
+        StackLayout::Tiled => stack_split.to_owned(),
+    };
+    let dimension = main_position.dimension();
+    match ws.nodes.len() {
+        1 => Some(format!("[con_id={new_id}] focus; {workspace_split}")),
+        2 => {
+            let (Some(stack), Some(main)) = (ws.nodes.first(), ws.nodes.last()) else {
+                log::warn!("stack_main: workspace {} lost its nodes, skipping", ws.id);
+                return None;
+            };
+
+            let cmd = if stack.is_window() {
+                // Decide by the event rather than by position: sway doesn't always
+                // open the new window after the old one.
+                let (stack_win, main_win) = if stack.id == new_id {
+                    (stack, main)
+                } else {
+                    (main, stack)
+                };
+                // The window that becomes the stack has to be the first node.
+                let swap = if stack_win.id == stack.id {
+                    String::new()
+                } else {
+                    format!(
+                        "[con_id={}] swap container with con_id {}; ",
+                        stack.id, main.id
+                    )
+                };
+                format!(
+                    "{swap}[con_id={}] focus; {}; resize set {dimension} {amount}; [con_id={}] focus",
+                    stack_win.id, layout, main_win.id
+                )
+            } else if let Some(node) = stack.find_as_ref(|n| n.id == new_id) {
+                format!(
+                    "[con_id={}] focus; swap container with con_id {}; [con_id={}] focus",
+                    main.id, node.id, node.id
+                )
+            } else {
+                String::from("nop event container not in stack")
+            };
+            Some(cmd)
+        }
+        3 => {
+            let Some(main) = ws
+                .nodes
+                .iter()
+                .skip(1)
+                .find(|n| n.is_window() && n.id != new_id)
+            else {
+                log::warn!("stack_main: main window not found, skipping");
+                return None;
+            };
+            let Some(stack) = ws.nodes.first() else {
+                log::warn!("stack_main: stack container not found, skipping");
+                return None;
+            };
+            let stack_mark = format!("_stack_{}", stack.id);
+
+            // Moving to a window of the stack places the new window right after it,
+            // the swap below then leaves the old main window there.
+            let (target, top) = match insert {
+                Some(StackInsert::Top) => (stack.nodes.first().unwrap_or(stack), true),
+                Some(StackInsert::Bottom) => (stack.nodes.last().unwrap_or(stack), false),
+                None => (stack, false),
+            };
+            // Right after the first window is one step from the top.
+            let to_top = if top && target.id != stack.id {
+                let direction = match stack.layout {
+                    NodeLayout::Tabbed | NodeLayout::SplitH => "left",
+                    _ => "up",
+                };
+                format!("; [con_id={}] move {direction}", main.id)
+            } else {
+                String::new()
+            };
+
+            Some(format!(
+                "[con_id={}] mark --add {}; [con_id={}] focus; move container to mark {}; [con_mark={}] unmark {}; [con_id={}] focus; swap container with con_id {}{to_top}; [con_id={}] focus",
+                target.id,
+                stack_mark,
+                new_id,
+                stack_mark,
+                stack_mark,
+                stack_mark,
+                main.id,
+                new_id,
+                new_id
+            ))
+        }
+        _ => None,
+    }
+}
+
 /// Stack‑main layout manager.
 ///
 /// Maintains:
@@ -37,171 +190,274 @@ pub struct StackMain {
     size: u8,
     /// How the stack area is laid out (`Tabbed`, `Stacked`, or `Tiled`).
     stack_layout: StackLayout,
+    /// Where the main area sits relative to the stack.
+    main_position: MainPosition,
+    /// `app_id`/class patterns of windows that are left alone like floating windows.
+    layout_ignore: Vec<String>,
+    /// Where windows leaving main go and the smallest main area.
+    settings: StackMainSettings,
+    /// Inner gap last measured on a workspace, for workspaces it can't be measured on.
+    last_gap: Option<i32>,
+    /// Last handled event kind and container ID, used to skip duplicates.
+    last_event: Option<(WindowChange, i64)>,
+    /// Last time an event was handled, used together with `last_event`.
+    last_event_time: Option<Instant>,
+    /// Only log the commands instead of running them.
+    dry_run: bool,
 }
 
 impl StackMain {
-    /// Entry point for a stack‑main layout pass.
+    /// Spawn a background task that sequentially handles stack‑main layout events.
     ///
-    /// Creates a `StackMain` instance with given `size` and `stack_layout`,
-    /// and dispatches the `WindowEvent` to the appropriate handler method.
+    /// The returned `StackMainSender` should be used to send `Box<WindowEvent>`
+    /// (along with the tree of its event pass and the workspace's main area size,
+    /// stack layout and main position) to the stack‑main manager from the `MessageHandler`.
     ///
     /// # Arguments
-    /// - `event`: The event to process (wrapped in `Box`).
-    /// - `size`: Main area size in percent.
-    /// - `stack_layout`: Layout for the stack area (`tabbed` / `stacked` / `tiled`).
-    pub async fn handle(event: Box<WindowEvent>, size: u8, stack_layout: StackLayout) {
-        if let Ok(mut manager) = Self::new(size, stack_layout).await {
-            manager.handle(event).await;
-        }
+    /// - `layout_ignore`: `app_id`/class patterns of windows that are never tiled.
+    /// - `settings`: Where windows leaving main go and the smallest main area.
+    /// - `dry_run`: Only log the commands instead of running them.
+    ///
+    /// # Return
+    /// `StackMainSender` for forwarding events to stack‑main.
+    pub fn spawn_handler(
+        layout_ignore: Vec<String>,
+        settings: StackMainSettings,
+        dry_run: bool,
+    ) -> StackMainSender {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            match Self::new(layout_ignore, settings, dry_run).await {
+                Ok(mut manager) => {
+                    log::debug!("stack_main manager: handler task started");
+                    while let Some((event, tree, size, stack_layout, main_position)) =
+                        rx.recv().await
+                    {
+                        manager.size = size;
+                        manager.stack_layout = stack_layout;
+                        manager.main_position = main_position;
+                        manager.handle(event, tree).await;
+                    }
+                    log::debug!("stack_main manager: handler task stopped");
+                }
+                Err(e) => {
+                    log::error!("stack_main manager: failed to initialize: {e}");
+                }
+            }
+        });
+
+        tx
     }
 
     /// Create a new `StackMain` instance.
     ///
-    /// Connects to Sway IPC and initializes internal layout parameters.
-    pub async fn new(size: u8, stack_layout: StackLayout) -> Result<Self> {
+    /// Connects to Sway IPC, the main area size, stack layout and main position are set per event.
+    async fn new(
+        layout_ignore: Vec<String>,
+        settings: StackMainSettings,
+        dry_run: bool,
+    ) -> Result<Self> {
         let connection = Connection::new().await?;
         Ok(Self {
             connection,
-            size,
-            stack_layout,
+            size: STACK_MAIN_DEFAULT_SIZE,
+            stack_layout: StackLayout::Stacked,
+            main_position: MainPosition::Right,
+            layout_ignore,
+            settings,
+            last_gap: None,
+            last_event: None,
+            last_event_time: None,
+            dry_run,
         })
     }
 
+    /// Whether workspace `ws` has the shape of a stack‑main workspace: a stack container
+    /// followed by the main window, or a single window.
+    pub fn has_shape(ws: &Node) -> bool {
+        if ws.iter().filter(|n| n.is_window()).count() < 2 {
+            return true;
+        }
+        matches!(ws.nodes.as_slice(), [stack, main] if !stack.is_window() && main.is_window())
+    }
+
+    /// Size of the main area of workspace `ws` in percent: `self.size`, raised so the
+    /// main area is at least `settings.min_main_px` wide (or high).
+    ///
+    /// When even the largest main area would be smaller than that, warns and keeps
+    /// `self.size` rather than squeezing the stack away.
+    fn main_size(&self, ws: &Node) -> u8 {
+        let Some(min_px) = self.settings.min_main_px else {
+            return self.size;
+        };
+        let total = match self.main_position {
+            MainPosition::Right => ws.rect.width,
+            MainPosition::Bottom => ws.rect.height,
+        };
+        let Some(total) = u32::try_from(total).ok().filter(|&total| total > 0) else {
+            return self.size;
+        };
+        let floor = (u64::from(min_px) * 100).div_ceil(u64::from(total));
+        let max = *SIZE_RANGE.end() as u64;
+        if floor > max {
+            log::warn!(
+                "stack_main: workspace is only {total}px, a main area of {min_px}px doesn't fit, keeping {}%",
+                self.size
+            );
+            return self.size;
+        }
+        self.size.max(u8::try_from(floor).unwrap_or(self.size))
+    }
+
+    /// The `resize set` amount giving `percent` of workspace `ws` to the main area
+    /// or the stack, see `resize_amount`.
+    ///
+    /// The inner gap is measured on `ws`, or the last one measured is used.
+    fn resize_amount(&mut self, ws: &Node, percent: u8) -> String {
+        if !self.settings.gap_aware_resize {
+            return resize_amount(percent, 0, 2, None);
+        }
+        if let Some(gap) = ws.inner_gap() {
+            self.last_gap = Some(gap);
+        }
+        let total = match self.main_position {
+            MainPosition::Right => ws.rect.width,
+            MainPosition::Bottom => ws.rect.height,
+        };
+        resize_amount(percent, total, 2, self.last_gap)
+    }
+
+    /// Whether `event` repeats the last handled event, for the same window and
+    /// within `DUPLICATE_EVENT_WINDOW`. Records `event` as the last one otherwise.
+    fn is_duplicate(&mut self, event: &WindowEvent) -> bool {
+        let current = (event.change, event.container.id);
+        if self.last_event == Some(current)
+            && self
+                .last_event_time
+                .is_some_and(|time| time.elapsed() < DUPLICATE_EVENT_WINDOW)
+        {
+            return true;
+        }
+        self.last_event = Some(current);
+        self.last_event_time = Some(Instant::now());
+        false
+    }
+
     /// Handle a `WindowChange::New` event for stack‑main layout.
     ///
     /// Adjusts the workspace layout when a new window appears:
     /// - Layout‑1 (1 node): split horizontally and place the new window in main.
     /// - Layout‑2 (2 nodes): mark one node as stack, apply stack layout, and position main.
-    /// - Layout‑3 (3+ nodes in stack): reorganize stack using marks and swaps.
-    async fn on_new_window(&mut self, event: &WindowEvent) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
-        let node = tree
-            .find_as_ref(|n| n.id == event.container.id)
-            .unwrap_or_else(|| panic!("no node found with id {}", event.container.id));
-        let ws = node.get_workspace().await?;
-        if should_skip_layout_of_workspace(&ws) {
+    /// - Layout‑3 (3+ nodes in stack): reorganize stack using marks and swaps, the old
+    ///   main window going to the end of the stack picked by `settings.insert`.
+    async fn on_new_window(&mut self, event: &WindowEvent, tree: &Node) -> Result<()> {
+        // The tree can change between the event and the query, skip stale events.
+        let Some(node) = tree.find_as_ref(|n| n.id == event.container.id) else {
+            log::warn!(
+                "stack_main: no node found with id {} (stale event), skipping",
+                event.container.id
+            );
+            return Ok(());
+        };
+        let Some(wstree) = node.find_workspace_in(tree) else {
+            log::warn!(
+                "stack_main: no workspace found for node {}, skipping",
+                node.id
+            );
+            return Ok(());
+        };
+        if is_special_workspace_node(wstree) {
             log::debug!("skip stack_main layout of \"special\" workspace");
             return Ok(());
         }
 
+        if wstree.is_tabbed_or_stacked_workspace() {
+            log::debug!("skip stack_main layout of tabbed/stacked workspace");
+            return Ok(());
+        }
+
         if node.is_floating() || node.is_full_screen() {
             log::debug!("skip stack_main layout of \"floating\" \"fullscreen\" workspace");
             return Ok(());
         }
 
-        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+        if node.matches_app(&self.layout_ignore) {
+            log::debug!("skip stack_main layout of ignored app window {}", node.id);
+            return Ok(());
+        }
+
         log::debug!("new_window id: {}", event.container.id);
         log::debug!("workspace nodes len: {}", wstree.nodes.len());
-        let layout = match self.stack_layout {
-            StackLayout::Tabbed => "split v; layout tabbed",
-            StackLayout::Stacked => "split v; layout stacking",
-            StackLayout::Tiled => "split v",
+        let amount = self.resize_amount(wstree, 100 - self.main_size(wstree));
+        let Some(cmd) = new_window_cmd(
+            wstree,
+            event.container.id,
+            &self.stack_layout,
+            self.main_position,
+            self.settings.insert,
+            &amount,
+        ) else {
+            return Ok(());
         };
-        match wstree.nodes.len() {
-            1 => {
-                let cmd = format!("[con_id={}] focus; split h", event.container.id);
-                self.connection.run_command(cmd).await?;
-                Ok(())
-            }
-            2 => {
-                let main = wstree.nodes.last().expect("main window not found");
-                let stack = wstree.nodes.first().expect("stack container not found");
-
-                let cmd = if stack.is_window() {
-                    format!(
-                        "[con_id={}] focus; {}; resize set width {}; [con_id={}] focus",
-                        stack.id,
-                        layout,
-                        (100 - self.size),
-                        main.id
-                    )
-                } else if let Some(node) = stack.find_as_ref(|n| n.id == event.container.id) {
-                    format!(
-                        "[con_id={}] focus; swap container with con_id {}; [con_id={}] focus",
-                        main.id, node.id, node.id
-                    )
-                } else {
-                    String::from("nop event container not in stack")
-                };
-
-                self.connection.run_command(cmd).await?;
-                Ok(())
-            }
-            3 => {
-                let main = wstree
-                    .nodes
-                    .iter()
-                    .skip(1)
-                    .find(|n| n.is_window() && n.id != event.container.id)
-                    .expect("main window not found");
-                let stack = wstree.nodes.first().expect("stack container not found");
-                let stack_mark = format!("_stack_{}", stack.id);
-
-                let cmd = format!(
-                    "[con_id={}] mark --add {}; [con_id={}] focus; move container to mark {}; [con_mark={}] unmark {}; [con_id={}] focus; swap container with con_id {}; [con_id={}] focus",
-                    stack.id,
-                    stack_mark,
-                    event.container.id,
-                    stack_mark,
-                    stack_mark,
-                    stack_mark,
-                    main.id,
-                    event.container.id,
-                    event.container.id
-                );
-
-                log::debug!("new_window: {cmd}");
-
-                self.connection.run_command(cmd).await?;
-                Ok(())
-            }
-            _ => Ok(()),
-        }
+        log::debug!("new_window: {cmd}");
+        run_command(&mut self.connection, cmd, self.dry_run).await?;
+        Ok(())
     }
 
     /// Handle a `WindowChange::Close` event for stack‑main layout.
     ///
     /// Adjusts layout when a window is closed, usually by:
-    /// - Moving the stack back to `splith` or resizing it if only one window remains.
-    async fn on_close_window(&mut self, event: &WindowEvent) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
+    /// - Moving the stack back to `splith` (`splitv` with the main area at the bottom)
+    ///   if only one window remains, or moving a stack window into the main area.
+    async fn on_close_window(&mut self, event: &WindowEvent, tree: &Node) -> Result<()> {
         let ws = get_focused_workspace(&mut self.connection).await?;
         if should_skip_layout_of_workspace(&ws) {
             log::debug!("skip stack_main layout of \"special\" workspace");
             return Ok(());
         }
 
-        let wstree = tree.find_as_ref(|n| n.id == ws.id).unwrap();
+        let Some(wstree) = tree.find_as_ref(|n| n.id == ws.id) else {
+            log::warn!("stack_main: workspace {} not in the tree, skipping", ws.num);
+            return Ok(());
+        };
 
         if wstree.nodes.len() == 1
             && let Some(stack) = wstree.nodes.iter().find(|n| n.id != event.container.id)
         {
-            let stack_current = stack
+            let Some(stack_current) = stack
                 .find_as_ref(|n| n.is_window() && n.focused)
-                .unwrap_or_else(|| {
-                    stack
-                        .find_as_ref(|n| n.visible.unwrap_or(false))
-                        .expect("stack should have a visible node")
-                });
+                .or_else(|| stack.find_as_ref(|n| n.visible.unwrap_or(false)))
+            else {
+                log::warn!(
+                    "stack_main: stack {} has no visible node, skipping",
+                    stack.id
+                );
+                return Ok(());
+            };
 
+            // Moving across the stack leaves it, towards the main area.
+            let (stack_exit, main_side) = match self.main_position {
+                MainPosition::Right => ("layout splith; move up", "right"),
+                MainPosition::Bottom => ("layout splitv; move left", "down"),
+            };
             let cmd = if wstree.iter().filter(|n| n.is_window()).count() == 1 {
                 log::debug!("on_close_window, count 1, stack_id: {}", stack_current.id);
-                format!(
-                    "[con_id={}] focus; layout splith; move up",
-                    stack_current.id
-                )
+                format!("[con_id={}] focus; {stack_exit}", stack_current.id)
             } else {
                 log::debug!(
                     "on_close_window, count more than 1, stack_id: {}",
                     stack_current.id
                 );
+                let amount = self.resize_amount(wstree, self.main_size(wstree));
                 format!(
-                    "[con_id={}] focus; move right; resize set width {}",
-                    stack_current.id, self.size
+                    "[con_id={}] focus; move {main_side}; resize set {} {amount}",
+                    stack_current.id,
+                    self.main_position.dimension(),
                 )
             };
             log::debug!("close_window: {cmd}");
-            self.connection.run_command(cmd).await?;
+            run_command(&mut self.connection, cmd, self.dry_run).await?;
         }
 
         Ok(())
@@ -213,20 +469,18 @@ impl StackMain {
     /// - If it moves within the same workspace, treat it as a new window layout.
     /// - If it moves to another workspace, call `on_new_window` for the target workspace
     ///   and `on_close_window` for the source workspace.
-    async fn on_move_window(&mut self, event: &WindowEvent) -> Result<()> {
-        let tree = self.connection.get_tree().await?;
-
+    async fn on_move_window(&mut self, event: &WindowEvent, tree: &Node) -> Result<()> {
         let Some(node) = tree.find_as_ref(|n| n.id == event.container.id) else {
             log::warn!("no node found with id {}", event.container.id);
             return Ok(());
         };
 
-        let Ok(ws) = node.get_workspace().await else {
+        let Some(ws) = node.find_workspace_in(tree) else {
             log::warn!("node had no workspace");
-            return self.on_close_window(event).await;
+            return self.on_close_window(event, tree).await;
         };
 
-        if should_skip_layout_of_workspace(&ws) {
+        if is_special_workspace_node(ws) {
             log::debug!("skip stack_main layout of \"special\" workspace");
             return Ok(());
         }
@@ -236,20 +490,35 @@ impl StackMain {
             return Ok(());
         }
 
+        if node.matches_app(&self.layout_ignore) {
+            log::debug!("skip stack_main layout of ignored app window {}", node.id);
+            return Ok(());
+        }
+
         let focused_ws = get_focused_workspace(&mut self.connection).await?;
 
         if ws.id == focused_ws.id {
-            log::debug!("move_window within workspace: {}", ws.num);
-            return self.on_new_window(event).await;
+            log::debug!("move_window within workspace: {:?}", ws.num);
+            return self.on_new_window(event, tree).await;
         }
 
-        log::debug!("move_window to other workspace: {}", ws.num);
-        self.on_new_window(event).await?;
-        self.on_close_window(event).await
+        log::debug!("move_window to other workspace: {:?}", ws.num);
+        self.on_new_window(event, tree).await?;
+        // The commands above changed the tree, the source workspace needs a fresh one.
+        let tree = self.connection.get_tree().await?;
+        self.on_close_window(event, &tree).await
     }
 }
 
 impl WindowEventHandler for StackMain {
+    /// The tree is only needed for the events this manager acts on.
+    fn needs_tree(change: WindowChange) -> bool {
+        matches!(
+            change,
+            WindowChange::New | WindowChange::Close | WindowChange::Move | WindowChange::Floating
+        )
+    }
+
     /// Handle a `WindowEvent` in the stack‑main layout manager.
     ///
     /// Dispatches:
@@ -258,23 +527,42 @@ impl WindowEventHandler for StackMain {
     /// - `Move` → `on_move_window`.
     /// - `Floating` → `on_close_window` (if floated) or `on_new_window` (if un‑floated).
     ///   Others are logged and ignored.
-    async fn handle(&mut self, event: Box<WindowEvent>) {
+    async fn handle(&mut self, event: Box<WindowEvent>, tree: Option<Arc<Node>>) {
+        if !Self::needs_tree(event.change) {
+            log::debug!("stack_main not handling event: {:?}", event.change);
+            return;
+        }
+        if self.is_duplicate(&event) {
+            log::debug!(
+                "stack_main: duplicate {:?} event for {}, skipping",
+                event.change,
+                event.container.id
+            );
+            return;
+        }
+        let tree = match tree_or_fetch(&mut self.connection, tree).await {
+            Ok(tree) => tree,
+            Err(e) => {
+                log::error!("stack_main layout err: {e}");
+                return;
+            }
+        };
         match event.change {
             WindowChange::New => {
                 log::debug!("stack_main handler handling event: {:?}", event.change);
-                if let Err(e) = self.on_new_window(&event).await {
+                if let Err(e) = self.on_new_window(&event, &tree).await {
                     log::error!("stack_main layout err: {e}");
                 }
             }
             WindowChange::Close => {
                 log::debug!("stack_main handler handling event: {:?}", event.change);
-                if let Err(e) = self.on_close_window(&event).await {
+                if let Err(e) = self.on_close_window(&event, &tree).await {
                     log::error!("stack_main layout err: {e}");
                 }
             }
             WindowChange::Move => {
                 log::debug!("stack_main handler handling event: {:?}", event.change);
-                if let Err(e) = self.on_move_window(&event).await {
+                if let Err(e) = self.on_move_window(&event, &tree).await {
                     log::error!("stack_main layout err: {e}");
                 }
             }
@@ -285,10 +573,10 @@ impl WindowEventHandler for StackMain {
                     event.container.is_floating()
                 );
                 if event.container.is_floating() {
-                    if let Err(e) = self.on_close_window(&event).await {
+                    if let Err(e) = self.on_close_window(&event, &tree).await {
                         log::error!("stack_main layout err: {e}");
                     }
-                } else if let Err(e) = self.on_new_window(&event).await {
+                } else if let Err(e) = self.on_new_window(&event, &tree).await {
                     log::error!("stack_main layout err: {e}");
                 }
             }
@@ -298,3 +586,114 @@ impl WindowEventHandler for StackMain {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    /// A node as `get_tree` returns it: a window when `app_id` is set, a container
+    /// laid out as `layout` otherwise.
+    fn node(
+        id: i64,
+        node_type: &str,
+        layout: &str,
+        app_id: Option<&str>,
+        nodes: Vec<Value>,
+    ) -> Value {
+        let rect = json!({"x": 0, "y": 0, "width": 1000, "height": 800});
+        json!({
+            "id": id,
+            "name": app_id,
+            "type": node_type,
+            "border": "none",
+            "current_border_width": 0,
+            "layout": layout,
+            "orientation": "none",
+            "percent": null,
+            "rect": rect,
+            "window_rect": rect,
+            "deco_rect": rect,
+            "geometry": rect,
+            "urgent": false,
+            "focused": false,
+            "focus": [],
+            "nodes": nodes,
+            "floating_nodes": [],
+            "sticky": false,
+            "marks": [],
+            "fullscreen_mode": 0,
+            "app_id": app_id,
+            "pid": app_id.map(|_| 1000 + id),
+            "shell": app_id.map(|_| "xdg_shell"),
+            "inhibit_idle": false,
+            "idle_inhibitors": {"user": "none", "application": "none"},
+            "visible": true,
+            "num": (node_type == "workspace").then_some(1),
+        })
+    }
+
+    fn window(id: i64) -> Value {
+        node(id, "con", "none", Some("foot"), Vec::new())
+    }
+
+    fn workspace(nodes: Vec<Value>) -> Node {
+        serde_json::from_value(node(100, "workspace", "splith", None, nodes))
+            .expect("valid workspace node")
+    }
+
+    fn cmd(ws: &Node, new_id: i64) -> Option<String> {
+        new_window_cmd(
+            ws,
+            new_id,
+            &StackLayout::Stacked,
+            MainPosition::Right,
+            None,
+            "30 ppt",
+        )
+    }
+
+    #[test]
+    fn first_window_is_main() {
+        let ws = workspace(vec![window(1)]);
+        assert_eq!(cmd(&ws, 1).as_deref(), Some("[con_id=1] focus; split h"));
+    }
+
+    #[test]
+    fn second_window_starts_the_stack_when_opened_after_the_first() {
+        let ws = workspace(vec![window(1), window(2)]);
+        assert_eq!(
+            cmd(&ws, 2).as_deref(),
+            Some(
+                "[con_id=1] swap container with con_id 2; [con_id=2] focus; split v; \
+                 layout stacking; resize set width 30 ppt; [con_id=1] focus"
+            )
+        );
+    }
+
+    #[test]
+    fn second_window_starts_the_stack_when_opened_before_the_first() {
+        let ws = workspace(vec![window(2), window(1)]);
+        assert_eq!(
+            cmd(&ws, 2).as_deref(),
+            Some(
+                "[con_id=2] focus; split v; layout stacking; resize set width 30 ppt; \
+                 [con_id=1] focus"
+            )
+        );
+    }
+
+    #[test]
+    fn third_window_takes_main_and_pushes_main_into_the_stack() {
+        let stack = node(10, "con", "stacked", None, vec![window(2)]);
+        let ws = workspace(vec![stack, window(1), window(3)]);
+        assert_eq!(
+            cmd(&ws, 3).as_deref(),
+            Some(
+                "[con_id=10] mark --add _stack_10; [con_id=3] focus; move container to mark \
+                 _stack_10; [con_mark=_stack_10] unmark _stack_10; [con_id=1] focus; swap \
+                 container with con_id 3; [con_id=3] focus"
+            )
+        );
+    }
+}
@@ -1,6 +1,146 @@
 use super::super::traits::WindowEventHandler;
+use crate::node_ext::{NodeExt, app_pattern_matches};
+use crate::utils::run_command;
 use anyhow::Result;
-use swayipc_async::{Connection, WindowChange, WindowEvent};
+use std::sync::Arc;
+use std::time::Duration;
+use swayipc_async::{Connection, Node, WindowChange, WindowEvent};
+use tokio::task::JoinHandle;
+
+/// At most this many windows get their opacity set per focus event.
+const OPACITY_RAMP_MAX_WINDOWS: usize = 10;
+
+/// Default `--focus-fade-ms`, how long the fade of a focused window takes.
+pub const FOCUS_FADE_MS: u64 = 100;
+
+/// Dim windows by how recently they were focused, see `--opacity-ramp`.
+#[derive(Debug, Clone, Copy)]
+pub struct OpacityRamp {
+    /// Opacity of the least recently focused windows.
+    pub min: f32,
+    /// How much each step back in the focus history lowers the opacity.
+    pub step: f32,
+}
+
+impl OpacityRamp {
+    /// Opacity of the window focused `age` focus changes ago.
+    fn opacity(self, age: usize) -> f32 {
+        #[allow(clippy::cast_precision_loss)]
+        let dimmed = 1.0 - self.step * age as f32;
+        dimmed.max(self.min)
+    }
+
+    /// How many of the most recently focused windows need updating: every window
+    /// past the point where the ramp hits `min` was already set to `min` then.
+    fn windows_to_update(self) -> usize {
+        (0..OPACITY_RAMP_MAX_WINDOWS)
+            .find(|&age| self.opacity(age) <= self.min)
+            .map_or(OPACITY_RAMP_MAX_WINDOWS, |age| age + 1)
+    }
+}
+
+/// Opacity settings, see `--opacity-ramp`, `--app-opacity` and `--focus-fade-steps`.
+#[derive(Debug, Clone, Default)]
+pub struct OpacitySettings {
+    pub ramp: Option<OpacityRamp>,
+    /// `app_id`/class patterns and the base opacity of their windows.
+    pub app: Vec<(String, f32)>,
+    pub fade: Option<FocusFade>,
+}
+
+/// Fade a focused window in with a few `opacity` steps, see `--focus-fade-steps`.
+#[derive(Debug, Clone, Copy)]
+pub struct FocusFade {
+    /// How many `opacity` commands the fade is made of.
+    pub steps: u32,
+    /// How long the whole fade takes.
+    pub duration: Duration,
+}
+
+/// Opacity `cmd` gives the window it runs on: the last `opacity N` command of it
+/// without criteria, eg. `1` for `[tiling] opacity 0.8; opacity 1`.
+fn opacity_set_by(cmd: &str) -> Option<f32> {
+    cmd.split([';', ','])
+        .map(str::trim)
+        .filter(|c| !c.starts_with('['))
+        .filter_map(|c| c.strip_prefix("opacity "))
+        .filter_map(|value| value.trim().parse().ok())
+        .next_back()
+}
+
+impl FocusFade {
+    /// Command setting window `id` to the opacity of `step` of a fade from `from` to `to`.
+    fn step_cmd(self, id: i64, from: f32, to: f32, step: u32) -> String {
+        #[allow(clippy::cast_precision_loss)]
+        let opacity = from + (to - from) * step as f32 / self.steps as f32;
+        format!("[con_id={id}] opacity {opacity:.2}")
+    }
+
+    /// Run the steps after the first of the fade of window `id`, one per interval.
+    async fn run(self, id: i64, from: f32, to: f32, dry_run: bool) {
+        if self.steps < 2 {
+            return;
+        }
+        let mut connection = match Connection::new().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::debug!("workspace window focus manager fade, err: {e}");
+                return;
+            }
+        };
+        let interval = self.duration / self.steps;
+        for step in 2..=self.steps {
+            tokio::time::sleep(interval).await;
+            let cmd = self.step_cmd(id, from, to, step);
+            if let Err(e) = run_command(&mut connection, cmd, dry_run).await {
+                // The window was most likely closed
+                log::debug!("workspace window focus manager fade, err: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Sway criteria regex matching the same app ids as `pattern`, see `app_pattern_matches`.
+fn app_pattern_regex(pattern: &str) -> String {
+    pattern.strip_suffix('*').map_or_else(
+        || format!("^{}$", regex::escape(pattern)),
+        |prefix| format!("^{}", regex::escape(prefix)),
+    )
+}
+
+/// Focus and leave commands of a workspace taking the place of `--on-window-focus` and
+/// `--on-window-focus-leave` there, see `set-focus-hooks`. `None` keeps the global
+/// command, an empty one runs nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FocusHooks {
+    pub focus: Option<String>,
+    pub leave: Option<String>,
+}
+
+impl FocusHooks {
+    /// Whether the workspace uses the global commands.
+    pub fn is_global(&self) -> bool {
+        self.focus.is_none() && self.leave.is_none()
+    }
+
+    /// The command to run: the override if there is one, `global` otherwise.
+    fn resolve(over: Option<&String>, global: Option<&String>) -> Option<String> {
+        match over {
+            Some(cmd) if cmd.is_empty() => None,
+            Some(cmd) => Some(cmd.clone()),
+            None => global.cloned(),
+        }
+    }
+}
+
+/// Border widths in pixels of the focused and unfocused windows, see
+/// `--focused-border` and `--unfocused-border`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BorderWidths {
+    pub focused: Option<u32>,
+    pub unfocused: Option<u32>,
+}
 
 #[allow(clippy::struct_field_names)]
 #[derive(Debug)]
@@ -9,6 +149,25 @@ pub struct WindowFocus {
     window_focus_cmd: Option<String>,
     window_focus_leave_cmd: Option<String>,
     previously_focused_id: Option<i64>,
+    /// Leave command of the workspace the previously focused window was focused on.
+    previous_leave_cmd: Option<String>,
+    /// The previously focused window matched `focus_hook_exclude`.
+    previously_focused_excluded: bool,
+    /// `app_id`/class patterns of windows the focus and leave commands skip.
+    focus_hook_exclude: Vec<String>,
+    /// `app_id`/class patterns and the command run when one of their windows gains focus.
+    app_focus_cmds: Vec<(String, String)>,
+    opacity_ramp: Option<OpacityRamp>,
+    /// `app_id`/class patterns and the base opacity of their windows.
+    app_opacity: Vec<(String, f32)>,
+    /// Recently focused window ids and their base opacity, most recent first, for
+    /// the opacity ramp.
+    focus_history: Vec<(i64, f32)>,
+    borders: BorderWidths,
+    focus_fade: Option<FocusFade>,
+    /// The fade of the last focused window, aborted when focus moves on.
+    fade_handle: Option<JoinHandle<()>>,
+    dry_run: bool,
 }
 
 impl WindowFocus {
@@ -16,6 +175,11 @@ impl WindowFocus {
     pub async fn new(
         window_focus_cmd: Option<String>,
         window_focus_leave_cmd: Option<String>,
+        focus_hook_exclude: Vec<String>,
+        app_focus_cmds: Vec<(String, String)>,
+        opacity: OpacitySettings,
+        borders: BorderWidths,
+        dry_run: bool,
     ) -> Result<Self> {
         // We create the connection here, just once.
         let connection = Connection::new().await?;
@@ -24,9 +188,36 @@ impl WindowFocus {
             window_focus_cmd,
             window_focus_leave_cmd,
             previously_focused_id: None,
+            previous_leave_cmd: None,
+            previously_focused_excluded: false,
+            focus_hook_exclude,
+            app_focus_cmds,
+            opacity_ramp: opacity.ramp,
+            app_opacity: opacity.app,
+            focus_history: Vec::new(),
+            borders,
+            focus_fade: opacity.fade,
+            fade_handle: None,
+            dry_run,
         })
     }
 
+    /// Replace the connection to Sway, after the previous one was lost.
+    pub async fn reconnect(&mut self) -> Result<()> {
+        self.connection = Connection::new().await?;
+        Ok(())
+    }
+
+    /// Command run when a window gains focus, if any.
+    pub fn focus_cmd(&self) -> Option<&str> {
+        self.window_focus_cmd.as_deref()
+    }
+
+    /// Command run when a window loses focus, if any.
+    pub fn focus_leave_cmd(&self) -> Option<&str> {
+        self.window_focus_leave_cmd.as_deref()
+    }
+
     /// Private helper to execute commands
     async fn run_cmd(&mut self, cmd: Option<String>, context: &str, id: Option<i64>) {
         let Some(cmd_str) = cmd else { return };
@@ -37,34 +228,216 @@ impl WindowFocus {
             None => cmd_str,
         };
 
-        if let Err(e) = self.connection.run_command(final_cmd).await {
+        if let Err(e) = run_command(&mut self.connection, final_cmd, self.dry_run).await {
             // Note: Errors here are expected if the window was just closed (id no longer exists)
             log::debug!("workspace window focus manager {context}, err: {e}");
         }
     }
-}
 
-impl WindowEventHandler for WindowFocus {
-    async fn handle(&mut self, event: Box<WindowEvent>) {
+    /// Run the command of every `--on-focus-app` rule matching the focused window `node`.
+    async fn run_app_focus_cmds(&mut self, node: &Node) {
+        let Some(app) = node.app_id_or_class() else {
+            return;
+        };
+        let cmds = self
+            .app_focus_cmds
+            .iter()
+            .filter(|(pattern, _)| app_pattern_matches(pattern, app))
+            .map(|(_, cmd)| cmd.clone())
+            .collect::<Vec<_>>();
+        for cmd in cmds {
+            self.run_cmd(Some(cmd), "on_focus_app", None).await;
+        }
+    }
+
+    /// Base opacity of `node` from the first `--app-opacity` rule matching it.
+    fn app_opacity_of(&self, node: &Node) -> Option<f32> {
+        let app = node.app_id_or_class()?;
+        self.app_opacity
+            .iter()
+            .find(|(pattern, _)| app_pattern_matches(pattern, app))
+            .map(|&(_, opacity)| opacity)
+    }
+
+    /// Set every window of the `--app-opacity` apps to its base opacity, over what
+    /// the focus commands set. The ramp takes care of it when there is one.
+    async fn apply_app_opacity(&mut self) {
+        if self.app_opacity.is_empty() || self.opacity_ramp.is_some() {
+            return;
+        }
+        // Rules are applied last first so the first matching one wins
+        let cmd = self
+            .app_opacity
+            .iter()
+            .rev()
+            .flat_map(|(pattern, opacity)| {
+                let regex = app_pattern_regex(pattern);
+                [
+                    format!("[app_id=\"{regex}\"] opacity {opacity:.2}"),
+                    format!("[class=\"{regex}\"] opacity {opacity:.2}"),
+                ]
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        self.run_cmd(Some(cmd), "app_opacity", None).await;
+    }
+
+    /// Move window `id` with base opacity `base` to the front of the focus history
+    /// and re-apply the opacity ramp.
+    ///
+    /// All opacities are set with a single command, touching only the windows
+    /// whose opacity changed.
+    async fn apply_opacity_ramp(&mut self, id: i64, base: f32) {
+        let Some(ramp) = self.opacity_ramp else {
+            return;
+        };
+        self.focus_history.retain(|&(prev, _)| prev != id);
+        self.focus_history.insert(0, (id, base));
+        self.focus_history.truncate(OPACITY_RAMP_MAX_WINDOWS);
+
+        let cmd = self
+            .focus_history
+            .iter()
+            .take(ramp.windows_to_update())
+            .enumerate()
+            .map(|(age, (id, base))| {
+                format!("[con_id={id}] opacity {:.2}", base * ramp.opacity(age))
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        self.run_cmd(Some(cmd), "opacity_ramp", None).await;
+    }
+
+    /// The opacity the newly focused window `node` had while unfocused and the one
+    /// it gets now, `None` if they are the same or unknown.
+    ///
+    /// Must be called before the window moves to the front of the focus history.
+    fn fade_range(
+        &self,
+        node: &Node,
+        base: f32,
+        focus_cmd: Option<&str>,
+        leave_cmd: Option<&str>,
+    ) -> Option<(f32, f32)> {
+        self.focus_fade?;
+        let (from, to) = if let Some(ramp) = self.opacity_ramp {
+            let age = self
+                .focus_history
+                .iter()
+                .position(|&(id, _)| id == node.id)
+                .unwrap_or(OPACITY_RAMP_MAX_WINDOWS);
+            (base * ramp.opacity(age), base)
+        } else if self.app_opacity_of(node).is_some() {
+            // The app keeps its opacity whether focused or not
+            return None;
+        } else {
+            let from = opacity_set_by(leave_cmd?)?;
+            let to = focus_cmd.and_then(opacity_set_by).unwrap_or(1.0);
+            (from, to)
+        };
+        ((from - to).abs() >= 0.01).then_some((from, to))
+    }
+
+    /// Fade window `id` from opacity `from` to `to`: the first step right away,
+    /// over the opacity the focus commands just set, the others in the background.
+    async fn start_fade(&mut self, id: i64, (from, to): (f32, f32)) {
+        let Some(focus_fade) = self.focus_fade else {
+            return;
+        };
+        let cmd = focus_fade.step_cmd(id, from, to, 1);
+        self.run_cmd(Some(cmd), "focus_fade", None).await;
+        self.fade_handle = Some(tokio::spawn(focus_fade.run(id, from, to, self.dry_run)));
+    }
+
+    /// Give the newly focused window `id` the focused border and the window that
+    /// lost focus, `prev_id`, the unfocused one, in a single command.
+    async fn apply_borders(&mut self, id: i64, prev_id: Option<i64>) {
+        let mut cmds = Vec::with_capacity(2);
+        if let (Some(px), Some(prev_id)) = (self.borders.unfocused, prev_id)
+            && prev_id != id
+        {
+            cmds.push(format!("[con_id={prev_id}] border pixel {px}"));
+        }
+        if let Some(px) = self.borders.focused {
+            cmds.push(format!("[con_id={id}] border pixel {px}"));
+        }
+        if cmds.is_empty() {
+            return;
+        }
+        self.run_cmd(Some(cmds.join("; ")), "borders", None).await;
+    }
+
+    /// Handle `event` on a workspace whose focus commands are overridden by `hooks`.
+    ///
+    /// A window that loses focus gets the leave command of the workspace it was
+    /// focused on.
+    pub async fn handle_with_hooks(&mut self, event: Box<WindowEvent>, hooks: &FocusHooks) {
         match event.change {
             WindowChange::Focus => {
-                let leave_cmd = self.window_focus_leave_cmd.clone();
-                let focus_cmd = self.window_focus_cmd.clone();
+                let leave_cmd = self.previous_leave_cmd.take();
+                let focus_cmd =
+                    FocusHooks::resolve(hooks.focus.as_ref(), self.window_focus_cmd.as_ref());
+                let ws_leave_cmd =
+                    FocusHooks::resolve(hooks.leave.as_ref(), self.window_focus_leave_cmd.as_ref());
+                // Excluded windows keep their default appearance
+                let excluded = event.container.matches_app(&self.focus_hook_exclude);
+                let base = self.app_opacity_of(&event.container).unwrap_or(1.0);
+                // The previous window gets its unfocused opacity below, mid-fade or not
+                if let Some(handle) = self.fade_handle.take() {
+                    handle.abort();
+                }
+                let fade_range = if excluded {
+                    None
+                } else {
+                    self.fade_range(
+                        &event.container,
+                        base,
+                        focus_cmd.as_deref(),
+                        ws_leave_cmd.as_deref(),
+                    )
+                };
 
                 // 1. Apply 'leave' command to the PREVIOUS window
                 if let Some(prev_id) = self.previously_focused_id {
                     // optimization: don't run leave if focusing the same window
-                    if prev_id != event.container.id {
+                    if prev_id != event.container.id && !self.previously_focused_excluded {
                         self.run_cmd(leave_cmd, "on_window_focus_leave", Some(prev_id))
                             .await;
                     }
                 }
 
-                // 2. Apply 'focus' command to the NEW window
-                self.run_cmd(focus_cmd, "on_window_focus", None).await;
+                // 2. Apply 'focus' command to the NEW window, and those of its app
+                if !excluded {
+                    self.run_cmd(focus_cmd, "on_window_focus", None).await;
+                }
+                self.run_app_focus_cmds(&event.container).await;
+
+                // 3. Dim the other windows by how long ago they were focused, or
+                //    give the windows of some apps their own opacity
+                self.apply_opacity_ramp(event.container.id, base).await;
+                self.apply_app_opacity().await;
+
+                // 4. Set the border widths of the new and the previous window
+                self.apply_borders(event.container.id, self.previously_focused_id)
+                    .await;
 
-                // 3. Update state for next time
+                // 5. Fade the new window in, after the commands above set its
+                //    final opacity
+                if let Some(range) = fade_range {
+                    self.start_fade(event.container.id, range).await;
+                }
+
+                // 6. Update state for next time
                 self.previously_focused_id = Some(event.container.id);
+                self.previously_focused_excluded = excluded;
+                self.previous_leave_cmd = ws_leave_cmd;
+            }
+            WindowChange::New => {
+                if let Some(opacity) = self.app_opacity_of(&event.container) {
+                    let cmd = format!("opacity {opacity:.2}");
+                    self.run_cmd(Some(cmd), "app_opacity", Some(event.container.id))
+                        .await;
+                }
             }
             WindowChange::Close => {
                 // If the closed window was the one we were tracking, clear it
@@ -74,6 +447,8 @@ impl WindowEventHandler for WindowFocus {
                 {
                     self.previously_focused_id = None;
                 }
+                self.focus_history
+                    .retain(|&(id, _)| id != event.container.id);
             }
             _ => log::debug!(
                 "workspace name manager, not handling event: {:?}",
@@ -82,3 +457,9 @@ impl WindowEventHandler for WindowFocus {
         }
     }
 }
+
+impl WindowEventHandler for WindowFocus {
+    async fn handle(&mut self, event: Box<WindowEvent>, _tree: Option<Arc<Node>>) {
+        self.handle_with_hooks(event, &FocusHooks::default()).await;
+    }
+}
@@ -1,22 +1,179 @@
 use super::super::traits::WindowEventHandler;
+use crate::node_ext::{NodeExt, app_pattern_matches};
+use crate::server::events::{self, EventSender, PerswayEvent};
 use crate::utils;
 
-use anyhow::Result;
-use swayipc_async::{Connection, WindowChange, WindowEvent, Workspace};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::{PoisonError, RwLock};
+use swayipc_async::{Connection, Node, WindowChange, WindowEvent, Workspace};
+
+/// Default `--workspace-renaming-format`, names a workspace after the focused app.
+pub const DEFAULT_RENAMING_FORMAT: &str = "{num}: {name}";
+
+/// Default `--workspace-renaming-delay-ms`, how long a rename waits for more window events.
+pub const RENAMING_DELAY_MS: u64 = 100;
+
+/// Settings shared by every rename task.
+#[derive(Debug)]
+pub struct RenamerConfig {
+    /// Icon table, behind a lock so it can be reloaded on SIGHUP.
+    pub icons: RwLock<WorkspaceIcons>,
+    /// Format of generated names, see `format_workspace_name`.
+    pub format: String,
+    /// Collapse runs of identical icons into one.
+    pub dedup: bool,
+    /// Suffix collapsed icons with how many there were, eg. `×3`.
+    pub dedup_count: bool,
+    /// Leave transient windows (dialogs) out of the names.
+    pub ignore_transient: bool,
+    /// Workspaces named with `lock-name`, never renamed.
+    pub locked: RwLock<HashSet<utils::WorkspaceKey>>,
+    /// Only log the rename commands instead of running them.
+    pub dry_run: bool,
+}
+
+/// User supplied mapping of app name to the glyph shown in workspace names.
+#[derive(Debug, Default)]
+pub struct WorkspaceIcons {
+    /// JSON file the icons were loaded from, if any.
+    path: Option<PathBuf>,
+    /// App name (or `prefix*` pattern) to glyph, keys lowercased.
+    icons: BTreeMap<String, String>,
+    /// JSON file the title rules were loaded from, if any.
+    title_rules_path: Option<PathBuf>,
+    /// Window title regexes and their glyphs, checked in order before `icons`.
+    title_rules: Vec<(Regex, String)>,
+    /// Glyph for apps that have no entry, if not set the app name is used.
+    default_icon: Option<String>,
+}
+
+impl WorkspaceIcons {
+    /// Load the icon table from the JSON object at `path`, eg. `{"firefox": ""}`,
+    /// and the title rules from the JSON array at `title_rules_path`, eg.
+    /// `[["vim", ""]]`.
+    pub fn load(
+        path: Option<PathBuf>,
+        title_rules_path: Option<PathBuf>,
+        default_icon: Option<String>,
+    ) -> Result<Self> {
+        let icons = match &path {
+            Some(path) => read_icons(path)?,
+            None => BTreeMap::new(),
+        };
+        let title_rules = match &title_rules_path {
+            Some(path) => read_title_rules(path)?,
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path,
+            icons,
+            title_rules_path,
+            title_rules,
+            default_icon,
+        })
+    }
+
+    /// Re-read the icons and title rules files, keeping the current ones if that fails.
+    pub fn reload(&mut self) -> Result<()> {
+        let icons = self.path.as_deref().map(read_icons).transpose()?;
+        let title_rules = self
+            .title_rules_path
+            .as_deref()
+            .map(read_title_rules)
+            .transpose()?;
+        if let Some(icons) = icons {
+            self.icons = icons;
+        }
+        if let Some(title_rules) = title_rules {
+            self.title_rules = title_rules;
+        }
+        log::info!("reloaded workspace icons");
+        Ok(())
+    }
+
+    /// Whether an icons or title rules file is configured (and so can be reloaded).
+    pub fn has_file(&self) -> bool {
+        self.path.is_some() || self.title_rules_path.is_some()
+    }
+
+    /// The label to show for window `node`: the glyph of the first title rule
+    /// matching its title, or else the label of its app. `None` if it has neither.
+    fn window_label(&self, node: &Node) -> Option<String> {
+        let title_icon = node.name.as_deref().and_then(|title| {
+            self.title_rules
+                .iter()
+                .find(|(regex, _)| regex.is_match(title))
+                .map(|(_, icon)| icon.clone())
+        });
+        title_icon.or_else(|| get_app_name(node).map(|app| self.label_for(&app)))
+    }
+
+    /// The label to show for `app_name`: its icon, the default icon or the name itself.
+    ///
+    /// Exact entries win over `prefix*` patterns, longer prefixes over shorter ones.
+    fn label_for(&self, app_name: &str) -> String {
+        self.icons
+            .get(app_name)
+            .or_else(|| {
+                self.icons
+                    .iter()
+                    .filter(|(pattern, _)| {
+                        pattern.ends_with('*') && app_pattern_matches(pattern, app_name)
+                    })
+                    .max_by_key(|(pattern, _)| pattern.len())
+                    .map(|(_, icon)| icon)
+            })
+            .or(self.default_icon.as_ref())
+            .map_or_else(|| app_name.to_owned(), Clone::clone)
+    }
+}
+
+fn read_title_rules(path: &Path) -> Result<Vec<(Regex, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read title rules file {}", path.display()))?;
+    let rules: Vec<(String, String)> = serde_json::from_str(&contents)
+        .with_context(|| format!("invalid title rules file {}", path.display()))?;
+    rules
+        .into_iter()
+        .map(|(pattern, icon)| {
+            let regex = Regex::new(&pattern).with_context(|| {
+                format!("invalid title regex '{pattern}' in {}", path.display())
+            })?;
+            Ok((regex, icon))
+        })
+        .collect()
+}
+
+fn read_icons(path: &Path) -> Result<BTreeMap<String, String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read icons file {}", path.display()))?;
+    let icons: BTreeMap<String, String> = serde_json::from_str(&contents)
+        .with_context(|| format!("invalid icons file {}", path.display()))?;
+    Ok(icons
+        .into_iter()
+        .map(|(app, icon)| (app.to_lowercase(), icon))
+        .collect())
+}
 
 pub struct WorkspaceRenamer {
     connection: Connection,
+    events: EventSender,
+    config: Arc<RenamerConfig>,
 }
 
 fn should_skip_rename_of_workspace(workspace: &Workspace) -> bool {
     utils::is_persway_tmp_workspace(workspace) || utils::is_scratchpad_workspace(workspace)
 }
 
-fn get_app_name(event: &WindowEvent) -> Option<String> {
-    let app_id = event.container.app_id.as_ref().filter(|&id| !id.is_empty());
+fn get_app_name(node: &Node) -> Option<String> {
+    let app_id = node.app_id.as_ref().filter(|&id| !id.is_empty());
     // .and_then(|id| if id.is_empty() { None } else { Some(id) });
 
-    let name: Option<String> = event.container.name.as_ref().and_then(|name| {
+    let name: Option<String> = node.name.as_ref().and_then(|name| {
         if name.is_empty() {
             None
         } else {
@@ -24,7 +181,7 @@ fn get_app_name(event: &WindowEvent) -> Option<String> {
         }
     });
 
-    let class = event.container.window_properties.as_ref().and_then(|p| {
+    let class = node.window_properties.as_ref().and_then(|p| {
         p.class.as_ref().filter(|&class| !class.is_empty())
         // .and_then(|class| if class.is_empty() { None } else { Some(class) })
     });
@@ -39,16 +196,60 @@ fn get_app_name(event: &WindowEvent) -> Option<String> {
     })
 }
 
+/// The part of a workspace name that is kept when renaming it.
+///
+/// A leading number followed by `:`, a space or nothing (`3: web`, `3 web`, `3`)
+/// is kept as is so `workspace number` binds keep working. Otherwise everything
+/// before the first `:` is kept.
+pub fn workspace_name_prefix(name: &str) -> &str {
+    let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && matches!(name[digits..].chars().next(), None | Some(':' | ' ')) {
+        &name[..digits]
+    } else {
+        name.split(':').next().unwrap_or(name)
+    }
+}
+
+/// Join window icons with spaces, optionally collapsing consecutive duplicates.
+fn join_icons(icons: &[String], dedup: bool, dedup_count: bool) -> String {
+    if !dedup {
+        return icons.join(" ");
+    }
+    icons
+        .chunk_by(|a, b| a == b)
+        .map(|run| match run.len() {
+            n if n > 1 && dedup_count => format!("{}×{n}", run[0]),
+            _ => run[0].clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Fill in the `{num}`, `{name}`, `{icons}` and `{count}` placeholders of `format`.
+fn format_workspace_name(format: &str, num: &str, name: &str, icons: &str, count: usize) -> String {
+    format
+        .replace("{num}", num)
+        .replace("{name}", name)
+        .replace("{icons}", icons)
+        .replace("{count}", &count.to_string())
+        .trim()
+        .to_owned()
+}
+
 impl WorkspaceRenamer {
-    pub async fn handle(event: Box<WindowEvent>) {
-        if let Ok(mut manager) = Self::new().await {
-            manager.handle(event).await;
+    pub async fn handle(event: Box<WindowEvent>, events: EventSender, config: Arc<RenamerConfig>) {
+        if let Ok(mut manager) = Self::new(events, config).await {
+            manager.handle(event, None).await;
         }
     }
 
-    pub async fn new() -> Result<Self> {
+    pub async fn new(events: EventSender, config: Arc<RenamerConfig>) -> Result<Self> {
         let connection = Connection::new().await?;
-        Ok(Self { connection })
+        Ok(Self {
+            connection,
+            events,
+            config,
+        })
     }
 
     async fn rename_workspace(&mut self, event: WindowEvent) -> Result<()> {
@@ -58,16 +259,66 @@ impl WorkspaceRenamer {
             log::debug!("workspace name manager skip renaming workspace");
             return Ok(());
         }
+        let key = utils::WorkspaceKey::of(&focused_ws);
+        if self
+            .config
+            .locked
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(&key)
+        {
+            log::debug!("workspace name manager skip workspace {key}, its name is locked");
+            return Ok(());
+        }
+
+        let ws_num = workspace_name_prefix(&focused_ws.name);
+        if let Some(app_name) = get_app_name(&event.container) {
+            let tree = self.connection.get_tree().await?;
+            let windows = tree
+                .find_as_ref(|n| n.id == focused_ws.id)
+                .map(|ws| {
+                    // The iterator walks children last to first, flip it back.
+                    let mut windows = ws
+                        .iter()
+                        .filter(|n| n.is_window() || n.is_floating_window())
+                        .filter(|n| !(self.config.ignore_transient && n.is_transient()))
+                        .collect::<Vec<_>>();
+                    windows.reverse();
+                    windows
+                })
+                .unwrap_or_default();
 
-        let ws_num = focused_ws
-            .name
-            .split(':')
-            .next()
-            .unwrap_or(&focused_ws.name);
-        if let Some(app_name) = get_app_name(&event) {
-            let cmd = format!("rename workspace to {ws_num}: {app_name}");
+            let name = {
+                let icons = self
+                    .config
+                    .icons
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner);
+                let window_icons = windows
+                    .iter()
+                    .filter_map(|n| icons.window_label(n))
+                    .collect::<Vec<_>>();
+                let focused_label = icons
+                    .window_label(&event.container)
+                    .unwrap_or_else(|| icons.label_for(&app_name));
+                format_workspace_name(
+                    &self.config.format,
+                    ws_num,
+                    &focused_label,
+                    &join_icons(&window_icons, self.config.dedup, self.config.dedup_count),
+                    windows.len(),
+                )
+            };
+            let cmd = format!("rename workspace to {name}");
             log::debug!("workspace name manager, cmd: {cmd}");
-            self.connection.run_command(cmd).await?;
+            utils::run_command(&mut self.connection, cmd, self.config.dry_run).await?;
+            events::publish(
+                &self.events,
+                PerswayEvent::WorkspaceRenamed {
+                    workspace: key,
+                    name,
+                },
+            );
         } else {
             log::error!("workspace name manager failed to set a workspace name");
         }
@@ -77,7 +328,9 @@ impl WorkspaceRenamer {
 }
 
 impl WindowEventHandler for WorkspaceRenamer {
-    async fn handle(&mut self, event: Box<WindowEvent>) {
+    /// Renaming runs after a debounce, by then the tree of the event pass is
+    /// stale so a fresh one is always fetched.
+    async fn handle(&mut self, event: Box<WindowEvent>, _tree: Option<Arc<Node>>) {
         match event.change {
             WindowChange::Focus | WindowChange::Close => {
                 if let Err(e) = self.rename_workspace(*event).await {
@@ -91,3 +344,34 @@ impl WindowEventHandler for WorkspaceRenamer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::workspace_name_prefix;
+
+    #[test]
+    fn keeps_the_number_before_a_colon() {
+        assert_eq!(workspace_name_prefix("3: web"), "3");
+    }
+
+    #[test]
+    fn keeps_the_number_before_a_space() {
+        assert_eq!(workspace_name_prefix("3 web"), "3");
+    }
+
+    #[test]
+    fn keeps_a_bare_number() {
+        assert_eq!(workspace_name_prefix("3"), "3");
+    }
+
+    #[test]
+    fn keeps_a_name_without_a_number() {
+        assert_eq!(workspace_name_prefix("web"), "web");
+        assert_eq!(workspace_name_prefix("mail: inbox"), "mail");
+    }
+
+    #[test]
+    fn keeps_an_empty_name_empty() {
+        assert_eq!(workspace_name_prefix(""), "");
+    }
+}
@@ -1,5 +1,19 @@
-use swayipc_async::WindowEvent;
+use std::sync::Arc;
+use swayipc_async::{Node, WindowChange, WindowEvent};
+use tokio::sync::mpsc;
+
+/// Sender used to feed events, with the tree of their event pass, to a long-lived handler.
+pub type EventTreeSender = mpsc::UnboundedSender<(Box<WindowEvent>, Option<Arc<Node>>)>;
 
 pub trait WindowEventHandler {
-    async fn handle(&mut self, event: Box<WindowEvent>);
+    /// Whether handling a `change` event needs the sway tree.
+    ///
+    /// `MessageHandler` fetches the tree at most once per event and only when the
+    /// handler for the workspace asks for it.
+    fn needs_tree(_change: WindowChange) -> bool {
+        false
+    }
+
+    /// Handle `event`, `tree` is the tree fetched for this event pass (if any).
+    async fn handle(&mut self, event: Box<WindowEvent>, tree: Option<Arc<Node>>);
 }
@@ -0,0 +1,76 @@
+//! Persway state change events.
+//!
+//! The daemon publishes these on a broadcast channel whenever something a status
+//! bar might care about changes. Every client connected to the events socket gets
+//! a copy of each event as a line of JSON.
+
+use crate::layout::WorkspaceLayout;
+use crate::utils::WorkspaceKey;
+use serde_json::json;
+use tokio::sync::broadcast;
+
+/// How many events a slow subscriber may fall behind before it starts missing some.
+pub const EVENTS_CAPACITY: usize = 64;
+
+/// Sender half of the events broadcast channel.
+pub type EventSender = broadcast::Sender<PerswayEvent>;
+
+/// A state change published on the events socket.
+#[derive(Debug, Clone)]
+pub enum PerswayEvent {
+    /// The layout of a workspace was changed.
+    LayoutChanged {
+        workspace: WorkspaceKey,
+        layout: WorkspaceLayout,
+    },
+    /// A workspace was renamed by the workspace renamer.
+    WorkspaceRenamed {
+        workspace: WorkspaceKey,
+        name: String,
+    },
+    /// A sway key binding ran `command`.
+    Binding { command: String },
+    /// The daemon is about to exit.
+    Shutdown,
+}
+
+impl PerswayEvent {
+    /// Serialize the event as a single line of JSON (without the trailing newline).
+    ///
+    /// `workspace` is the workspace number, or its name for workspaces without one.
+    pub fn to_json(&self) -> String {
+        match self {
+            Self::LayoutChanged { workspace, layout } => json!({
+                "event": "layout",
+                "workspace": workspace_json(workspace),
+                "layout": layout.to_string(),
+            }),
+            Self::WorkspaceRenamed { workspace, name } => json!({
+                "event": "rename",
+                "workspace": workspace_json(workspace),
+                "name": name,
+            }),
+            Self::Binding { command } => json!({
+                "event": "binding",
+                "command": command,
+            }),
+            Self::Shutdown => json!({ "event": "shutdown" }),
+        }
+        .to_string()
+    }
+}
+
+fn workspace_json(key: &WorkspaceKey) -> serde_json::Value {
+    match key {
+        WorkspaceKey::Num(num) => json!(num),
+        WorkspaceKey::Name(name) => json!(name),
+    }
+}
+
+/// Publish `event` to all current subscribers.
+///
+/// Having no subscribers is the common case and not an error.
+pub fn publish(sender: &EventSender, event: PerswayEvent) {
+    log::debug!("publishing event: {event:?}");
+    let _ = sender.send(event);
+}
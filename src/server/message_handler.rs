@@ -5,19 +5,164 @@
 //! - Event dispatch to layout handlers (`Spiral`, `StackMain`) and `WindowFocus`.
 //! - Command handling for `PerswayCommand` such as layout changes and stack commands.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
-use anyhow::{Result, bail, ensure};
-use swayipc_async::{Connection, WindowEvent};
+use anyhow::{Context, Result, bail, ensure};
+use regex::Regex;
+use serde_json::json;
+use swayipc_async::{BindingEvent, Connection, Node, WindowChange, WindowEvent, Workspace};
 use tokio::sync::mpsc;
 use tokio::task;
 
 use super::command_handlers;
+use super::command_handlers::misc::snapshot::{Snapshot, SnapshotWindow};
 use super::event_handlers;
-use super::event_handlers::traits::WindowEventHandler;
+use super::event_handlers::misc::window_focus::{
+    BorderWidths, FocusFade, FocusHooks, OpacityRamp, OpacitySettings,
+};
+use super::event_handlers::misc::workspace_renamer::{
+    RenamerConfig, WorkspaceIcons, workspace_name_prefix,
+};
+use super::event_handlers::traits::{EventTreeSender, WindowEventHandler};
+use super::events::{self, EventSender, PerswayEvent};
 
-use crate::server::event_handlers::layout::spiral::Spiral;
-use crate::{commands::PerswayCommand, layout::WorkspaceLayout, utils};
+use crate::server::event_handlers::layout::{
+    centered_main::CenteredMain,
+    dwindle::Dwindle,
+    spiral::{Spiral, SpiralSender, SpiralSettings},
+    stack_main::{StackMain, StackMainSender, StackMainSettings},
+};
+use crate::{
+    commands::{DaemonArgs, PerswayCommand},
+    layout::{MainPosition, STACK_ZOOM_SIZE, SplitDir, StackLayout, WorkspaceLayout},
+    node_ext::{NodeExt, app_pattern_matches},
+    utils::{self, WorkspaceKey},
+};
+
+/// How many focused windows `focus-back` remembers.
+const FOCUS_HISTORY_LEN: usize = 32;
+
+/// How long after the last window event workspaces are left alone by `reconcile`,
+/// so it never acts on a layout the user or persway is still changing.
+const RECONCILE_QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+/// Commands `undo` knows how to undo, for its error message.
+const UNDOABLE_COMMANDS: &str =
+    "stack-swap-main, stack-swap-last-focused, stack-main-set-size and change-layout";
+
+/// Layouts `cycle-layout` goes through when none are given, `stack_main` being the
+/// one with the daemon's `--stack-main-default-*` settings.
+fn default_layout_cycle(stack_main: &WorkspaceLayout) -> Vec<WorkspaceLayout> {
+    vec![
+        WorkspaceLayout::Spiral,
+        stack_main.clone(),
+        WorkspaceLayout::Manual,
+    ]
+}
+
+/// The layout after `current` in `layouts`, wrapping around.
+///
+/// Layouts are matched by kind, so a `stack_main` with a different size still
+/// counts as `stack_main`. If `current` isn't in the list the first entry is used.
+fn next_layout(layouts: &[WorkspaceLayout], current: &WorkspaceLayout) -> WorkspaceLayout {
+    let next = layouts
+        .iter()
+        .position(|l| mem::discriminant(l) == mem::discriminant(current))
+        .map_or(0, |i| (i + 1) % layouts.len());
+    layouts[next].clone()
+}
+
+/// The sway mode a binding's `command` switches to, if it does, eg. `resize` for
+/// `mode "resize"`.
+fn binding_mode(command: &str) -> Option<String> {
+    let args = command
+        .split([';', ','])
+        .rev()
+        .find_map(|cmd| cmd.trim().strip_prefix("mode "))?;
+    let mode = args
+        .split_whitespace()
+        .filter(|arg| !arg.starts_with("--"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(mode.trim_matches('"').to_owned())
+}
+
+/// Whether `change` may change the number of tiled windows on the focused
+/// workspace, or focus another workspace, so smart gaps need checking.
+fn needs_gaps(change: WindowChange) -> bool {
+    matches!(
+        change,
+        WindowChange::New
+            | WindowChange::Close
+            | WindowChange::Move
+            | WindowChange::Floating
+            | WindowChange::Focus
+    )
+}
+
+/// Errors of `persway` commands that scripts may want to tell apart.
+///
+/// They travel inside an `anyhow::Error`, the daemon looks for them to put their
+/// `code` in the `fail:<CODE>: <message>` reply. Any other error gets `ERROR`.
+#[derive(Debug)]
+pub enum CommandError {
+    /// A command needs a layout the focused workspace doesn't have.
+    WrongLayout {
+        cmd: &'static str,
+        workspace: String,
+        layout: WorkspaceLayout,
+        /// Layouts the command works on, eg. `manual or spiral`.
+        required: &'static str,
+    },
+    /// A command arrived before the `MessageHandler` was set up.
+    DaemonNotInitialized,
+}
+
+impl CommandError {
+    /// Generic code of errors that aren't a `CommandError`.
+    pub const GENERIC_CODE: &'static str = "ERROR";
+
+    /// Stable, machine-readable code of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::WrongLayout { .. } => "WRONG_LAYOUT",
+            Self::DaemonNotInitialized => "DAEMON_NOT_INITIALIZED",
+        }
+    }
+
+    /// The code of `e` if it is a `CommandError`, `GENERIC_CODE` otherwise.
+    pub fn code_of(e: &anyhow::Error) -> &'static str {
+        e.downcast_ref::<Self>()
+            .map_or(Self::GENERIC_CODE, Self::code)
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongLayout {
+                cmd,
+                workspace,
+                layout,
+                required,
+            } => write!(
+                f,
+                "{cmd} only works on {required} workspaces.\n\
+                 Focused workspace: {workspace}\n\
+                 Current layout: {layout}\n\
+                 Fix: persway change-layout {}",
+                // The first of several layouts, to keep the fix a command
+                required.split(" or ").next().unwrap_or(required)
+            ),
+            Self::DaemonNotInitialized => write!(f, "daemon not initialized"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
 
 /// Configuration associated with a single workspace.
 ///
@@ -25,142 +170,1510 @@ use crate::{commands::PerswayCommand, layout::WorkspaceLayout, utils};
 #[derive(Debug)]
 pub struct WorkspaceConfig {
     layout: WorkspaceLayout,
+    /// The layout came from a default rather than a command, so it may be re-seeded.
+    seeded: bool,
+    /// The last layout other than `manual`, restored by `toggle-manual`.
+    previous_layout: Option<WorkspaceLayout>,
+    /// The layout was set by an `--app-layout` rule, so another rule may replace it.
+    from_app_rule: bool,
+    /// Main area size before `stack-zoom-toggle` zoomed in, `None` when not zoomed.
+    zoomed_from: Option<u8>,
+    /// Split direction forced on the spiral layout with `spiral-force-dir`.
+    spiral_dir: SplitDir,
+    /// Focus commands set with `set-focus-hooks`, in place of the global ones.
+    focus_hooks: FocusHooks,
+}
+
+/// A job of the relayout task, see `MessageHandler::spawn_relayout_handler`.
+#[derive(Debug)]
+enum RelayoutJob {
+    /// Lay out workspace `key` again.
+    Relayout(WorkspaceKey),
+    /// Rebuild workspace `key` from the `windows` of a snapshot, see `restore_workspace`.
+    Restore {
+        key: WorkspaceKey,
+        windows: Vec<SnapshotWindow>,
+        resize: bool,
+    },
+    /// Exchange the windows of workspaces `a` and `b`, see `swap_workspaces`.
+    Swap { a: WorkspaceKey, b: WorkspaceKey },
+}
+
+/// How to undo the last undoable command, see `undo`.
+#[derive(Debug)]
+enum UndoAction {
+    /// Swap back the old main window and the stack window that took its place.
+    SwapMain {
+        workspace: WorkspaceKey,
+        main: i64,
+        other: i64,
+    },
+    /// Resize the main area back to `size` percent.
+    SetSize { workspace: WorkspaceKey, size: u8 },
+    /// Switch back to `layout`.
+    Layout {
+        workspace: WorkspaceKey,
+        layout: WorkspaceLayout,
+    },
+}
+
+impl UndoAction {
+    fn workspace(&self) -> &WorkspaceKey {
+        match self {
+            Self::SwapMain { workspace, .. }
+            | Self::SetSize { workspace, .. }
+            | Self::Layout { workspace, .. } => workspace,
+        }
+    }
 }
 
 /// Main handler for all Sway events and `persway` commands.
 ///
 /// Stores:
-/// - Per‑workspace `WorkspaceConfig`s mapped by workspace number.
-/// - The default layout for new workspaces.
+/// - Per‑workspace `WorkspaceConfig`s mapped by `WorkspaceKey` (number, or name for named workspaces).
+/// - The default layout for new workspaces, globally, per output and for workspace
+///   names matching a regex (`--workspace-layout-rule`).
+/// - Layouts forced on a workspace when some app opens on it (`--app-layout`).
+/// - Named layouts for `apply-preset` (`--preset`).
 /// - A Sway `Connection` used for executing layout and rename commands.
 /// - A `WindowFocus` handler for opacity/mark‑based focus hooks.
-/// - A `mpsc::UnboundedSender` for forwarding events to the `Spiral` layout handler.
-/// - Optional `JoinHandle` for debounced workspace renaming.
+/// - A `SpiralSender`, an `EventTreeSender` and a `StackMainSender` for forwarding events
+///   to the `Spiral`, `Dwindle` and `StackMain` layout handlers, with the stack‑main and spiral
+///   settings to respawn them when the connection to Sway is lost.
+/// - The `stack_main` layout of the default `cycle-layout` cycle (`--stack-main-default-*`).
+/// - Optional `JoinHandle` for debounced workspace renaming, and how long renames wait.
+/// - `app_id`/class patterns of windows the layout handlers leave alone.
+/// - An `EventSender` for publishing state changes on the events socket.
+/// - Whether commands are only logged (`--dry-run`).
+/// - The ids of recently focused windows, most recent last, for `focus-back`.
+/// - How long a relayout waits after moving each window back (`--relayout-step-delay-ms`).
+/// - The sway modes layouts are paused in (`--pause-in-mode`), and the one sway is in if so.
+/// - When the last window event arrived, for `reconcile`.
+/// - The workspaces persway manages or leaves alone (`--managed-workspaces`,
+///   `--unmanaged-workspaces`), and whether focus hooks run on the ones left alone.
+/// - The inner gaps to use unless a workspace has a single tiled window (`--smart-gaps`),
+///   and which workspaces were last left without gaps.
+/// - How to undo the last command, if it was one `undo` can undo.
+/// - The ids of the windows moved away with `stash`, most recent last.
+/// - The ids of the windows pinned with `pin-toggle`.
 pub struct MessageHandler {
     connection: Connection,
-    workspace_config: HashMap<i32, WorkspaceConfig>,
+    workspace_config: HashMap<WorkspaceKey, WorkspaceConfig>,
     default_layout: WorkspaceLayout,
+    output_layouts: HashMap<String, WorkspaceLayout>,
+    workspace_layout_rules: Vec<(Regex, WorkspaceLayout)>,
+    app_layouts: Vec<(String, WorkspaceLayout)>,
+    presets: HashMap<String, WorkspaceLayout>,
     workspace_renaming: bool,
+    renamer_config: Arc<RenamerConfig>,
     window_focus_handler: event_handlers::misc::window_focus::WindowFocus,
-    spiral_tx: mpsc::UnboundedSender<Box<WindowEvent>>, // Sender to the Spiral event handler
+    spiral_tx: SpiralSender,        // Sender to the Spiral event handler
+    dwindle_tx: EventTreeSender,    // Sender to the Dwindle event handler
+    stack_main_tx: StackMainSender, // Sender to the StackMain event handler
+    stack_main_settings: StackMainSettings,
+    stack_main_default: WorkspaceLayout,
+    spiral_settings: SpiralSettings,
     rename_handle: Option<task::JoinHandle<()>>,
+    rename_delay: Duration,
+    layout_ignore: Vec<String>,
+    events: EventSender,
+    dry_run: bool,
+    focus_history: Vec<i64>,
+    smart_gaps: Option<u32>,
+    gapless: HashMap<WorkspaceKey, bool>,
+    relayout_tx: mpsc::UnboundedSender<RelayoutJob>,
+    pause_modes: Vec<String>,
+    paused_in_mode: Option<String>,
+    enabled: bool,
+    paused_hooks: bool,
+    last_event_at: Option<Instant>,
+    managed_workspaces: Vec<WorkspaceKey>,
+    unmanaged_workspaces: Vec<WorkspaceKey>,
+    unmanaged_focus_hooks: bool,
+    ignore_transient: bool,
+    undo: Option<UndoAction>,
+    stash: Vec<i64>,
+    pinned: Vec<i64>,
+    on_workspace_empty: Option<String>,
+    emptied: HashSet<WorkspaceKey>,
+}
+
+/// Role of window `node` of workspace `ws` laid out as `layout`: `floating`, `main`
+/// or `stack` in the layouts with a main area, `tiled` otherwise.
+fn window_role(ws: &Node, node: &Node, layout: &WorkspaceLayout) -> &'static str {
+    if node.is_floating() {
+        return "floating";
+    }
+    match layout {
+        WorkspaceLayout::StackMain { .. } => match ws.nodes.last() {
+            Some(main) if main.id != node.id && ws.nodes.len() > 1 => "stack",
+            _ => "main",
+        },
+        WorkspaceLayout::CenteredMain { .. } => match ws.nodes.as_slice() {
+            [_, main, _] if main.id == node.id => "main",
+            [_, _, _] => "stack",
+            _ => "tiled",
+        },
+        _ => "tiled",
+    }
+}
+
+/// Whether the container `node` of workspace `ws` laid out as `layout` is the stack
+/// (or one of the two stacks) of a layout with a main area.
+fn is_stack_container(ws: &Node, node: &Node, layout: &WorkspaceLayout) -> bool {
+    match layout {
+        WorkspaceLayout::StackMain { .. } => {
+            ws.nodes.len() > 1 && ws.nodes.first().is_some_and(|stack| stack.id == node.id)
+        }
+        WorkspaceLayout::CenteredMain { .. } => match ws.nodes.as_slice() {
+            [left, _, right] => left.id == node.id || right.id == node.id,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Push a `list-windows` line for every child of `parent` on workspace `ws`, in tree
+/// order, indented by `depth`.
+fn list_nodes(
+    ws: &Node,
+    parent: &Node,
+    layout: &WorkspaceLayout,
+    depth: usize,
+    lines: &mut Vec<String>,
+) {
+    let indent = "  ".repeat(depth);
+    for node in parent.nodes.iter().chain(&parent.floating_nodes) {
+        if node.is_window() || node.is_floating_window() {
+            lines.push(format!(
+                "{indent}window con_id={} role={} app_id={} focused={} title={:?}",
+                node.id,
+                window_role(ws, node, layout),
+                node.app_id_or_class().unwrap_or("unknown"),
+                node.focused,
+                node.name.as_deref().unwrap_or_default()
+            ));
+        } else {
+            let kind = if is_stack_container(ws, node, layout) {
+                "stack"
+            } else {
+                "container"
+            };
+            lines.push(format!(
+                "{indent}{kind} con_id={} layout={}",
+                node.id,
+                format!("{:?}", node.layout).to_lowercase()
+            ));
+            list_nodes(ws, node, layout, depth + 1, lines);
+        }
+    }
 }
 
 impl MessageHandler {
-    /// Create a new `MessageHandler` with default layout and focus hooks.
+    /// Create a new `MessageHandler` from the daemon arguments.
     ///
     /// # Arguments
-    /// - `default_layout`: Layout used for workspaces that haven’t been explicitly configured.
-    /// - `workspace_renaming`: If `true`, workspace names are updated based on running apps.
-    /// - `on_window_focus`: Optional Sway command run when a window gains focus.
-    /// - `on_window_focus_leave`: Optional Sway command run when focus leaves a window.
-    pub async fn new(
-        default_layout: WorkspaceLayout,
-        workspace_renaming: bool,
-        on_window_focus: Option<String>,
-        on_window_focus_leave: Option<String>,
-    ) -> Result<Self> {
+    /// - `args.default_layout`: Layout used for workspaces that haven’t been explicitly configured.
+    /// - `args.output_layout`: Per-output overrides of `default_layout`.
+    /// - `args.workspace_layout_rule`: Default layouts of workspaces by name regex.
+    /// - `args.app_layout`: Layouts a workspace switches to when an app opens on it.
+    /// - `args.preset`: Named layouts `apply-preset` switches to.
+    /// - `args.workspace_renaming`: If `true`, workspace names are updated based on running apps.
+    /// - `args.workspace_renaming_icons`: Optional JSON file mapping apps to workspace name icons.
+    /// - `args.workspace_renaming_title_rules`: Optional JSON file of title regexes and icons.
+    /// - `args.workspace_renaming_default_icon`: Icon for apps missing from the icons file.
+    /// - `args.workspace_renaming_format`: Format of generated workspace names.
+    /// - `args.workspace_renaming_dedup(_count)`: Collapse repeated icons (with a count).
+    /// - `args.workspace_renaming_delay_ms`: How long a rename waits for more window events.
+    /// - `args.on_window_focus`: Optional Sway command run when a window gains focus.
+    /// - `args.on_window_focus_leave`: Optional Sway command run when focus leaves a window.
+    /// - `args.focus_hook_exclude`: `app_id`/class patterns of windows both commands skip.
+    /// - `args.on_focus_app`: Sway commands run when a window of an app gains focus.
+    /// - `args.opacity_ramp`: Optional `[min, step]` for dimming windows by focus age.
+    /// - `args.app_opacity`: Base opacities of the windows of some apps.
+    /// - `args.focused_border`/`args.unfocused_border`: Optional border widths of
+    ///   focused and unfocused windows.
+    /// - `args.focus_fade_steps`/`args.focus_fade_ms`: Optional fade-in of focused windows.
+    /// - `args.layout_ignore`: `app_id`/class patterns of windows that are never auto-tiled.
+    /// - `args.stack_main_default_*`: The `stack_main` layout `cycle-layout` switches to by default.
+    /// - `args.stack_insert`: End of the `stack_main` stack windows leaving main go to.
+    /// - `args.stack_main_min_main_px`: Smallest size of the `stack_main` main area in pixels.
+    /// - `args.gap_aware_resize`: Size the `stack_main` main area between the inner gaps.
+    /// - `args.ignore_transient`: Leave dialogs out of the layouts and workspace names.
+    /// - `args.spiral_ratio`: Share of the space the older window keeps on a spiral split.
+    /// - `args.spiral_hysteresis`: How much longer a side has to be to change a spiral split.
+    /// - `args.spiral_throttle_ms`: Minimum time between two spiral layout passes.
+    /// - `args.spiral_resize_cooldown_ms`: How long spiral leaves resized windows alone.
+    /// - `args.relayout_step_delay_ms`: Pause after each window a relayout moves back.
+    /// - `args.pause_in_mode`: Sway modes in which window events don't reach the layouts.
+    /// - `args.managed_workspaces`/`args.unmanaged_workspaces`: Workspaces to lay out and
+    ///   rename, or to leave alone.
+    /// - `args.unmanaged_focus_hooks`: Run the focus hooks on workspaces left alone too.
+    /// - `args.smart_gaps`: Inner gaps of workspaces with more than one tiled window.
+    /// - `args.on_workspace_empty`: Command run when a workspace loses its last window.
+    /// - `args.dry_run`: Log sway commands instead of running them.
+    /// - `events`: Broadcast sender for layout and rename events.
+    pub async fn new(args: DaemonArgs, events: EventSender) -> Result<Self> {
+        let DaemonArgs {
+            default_layout,
+            output_layout,
+            workspace_layout_rule,
+            app_layout,
+            preset,
+            workspace_renaming,
+            workspace_renaming_icons,
+            workspace_renaming_title_rules,
+            workspace_renaming_default_icon,
+            workspace_renaming_format,
+            workspace_renaming_dedup,
+            workspace_renaming_dedup_count,
+            workspace_renaming_delay_ms,
+            on_window_focus,
+            on_window_focus_leave,
+            focus_hook_exclude,
+            on_focus_app,
+            opacity_ramp,
+            app_opacity,
+            focused_border,
+            unfocused_border,
+            focus_fade_steps,
+            focus_fade_ms,
+            layout_ignore,
+            stack_main_default_size,
+            stack_main_default_stack_layout,
+            stack_main_default_main_position,
+            stack_insert,
+            stack_main_min_main_px,
+            gap_aware_resize,
+            ignore_transient,
+            spiral_ratio,
+            spiral_hysteresis,
+            spiral_throttle_ms,
+            spiral_resize_cooldown_ms,
+            smart_gaps,
+            relayout_step_delay_ms,
+            pause_in_mode,
+            managed_workspaces,
+            unmanaged_workspaces,
+            unmanaged_focus_hooks,
+            on_workspace_empty,
+            dry_run,
+            ..
+        } = args;
+
+        let renamer_config = Arc::new(RenamerConfig {
+            icons: RwLock::new(WorkspaceIcons::load(
+                workspace_renaming_icons,
+                workspace_renaming_title_rules,
+                workspace_renaming_default_icon,
+            )?),
+            format: workspace_renaming_format,
+            dedup: workspace_renaming_dedup,
+            dedup_count: workspace_renaming_dedup_count,
+            ignore_transient,
+            locked: RwLock::new(HashSet::new()),
+            dry_run,
+        });
+
         let window_focus_handler = event_handlers::misc::window_focus::WindowFocus::new(
             on_window_focus,
             on_window_focus_leave,
+            focus_hook_exclude,
+            on_focus_app,
+            OpacitySettings {
+                ramp: opacity_ramp.map(|ramp| OpacityRamp {
+                    min: ramp[0],
+                    step: ramp[1],
+                }),
+                app: app_opacity,
+                fade: focus_fade_steps.map(|steps| FocusFade {
+                    steps,
+                    duration: Duration::from_millis(focus_fade_ms),
+                }),
+            },
+            BorderWidths {
+                focused: focused_border,
+                unfocused: unfocused_border,
+            },
+            dry_run,
         )
         .await?;
 
         let connection = Connection::new().await?;
 
-        // Initialize the spiral handler once
-        let spiral_tx = Spiral::spawn_handler();
+        // Initialize the spiral and dwindle handlers once
+        let spiral_settings = SpiralSettings {
+            ratio: spiral_ratio,
+            hysteresis: spiral_hysteresis,
+            throttle: Duration::from_millis(spiral_throttle_ms),
+            resize_cooldown: spiral_resize_cooldown_ms.map(Duration::from_millis),
+        };
+        let spiral_tx = Spiral::spawn_handler(layout_ignore.clone(), spiral_settings, dry_run);
+        let dwindle_tx = Dwindle::spawn_handler(layout_ignore.clone(), dry_run);
+        let stack_main_settings = StackMainSettings {
+            insert: stack_insert,
+            min_main_px: stack_main_min_main_px,
+            gap_aware_resize,
+        };
+        let stack_main_tx =
+            StackMain::spawn_handler(layout_ignore.clone(), stack_main_settings, dry_run);
 
         Ok(Self {
             connection,
             workspace_config: HashMap::new(),
             default_layout,
+            output_layouts: output_layout.into_iter().collect(),
+            workspace_layout_rules: workspace_layout_rule,
+            app_layouts: app_layout,
+            presets: preset.into_iter().collect(),
             workspace_renaming,
+            renamer_config,
             window_focus_handler,
             spiral_tx, // Store it
+            dwindle_tx,
+            stack_main_tx,
+            stack_main_settings,
+            stack_main_default: WorkspaceLayout::StackMain {
+                size: stack_main_default_size,
+                stack_layout: stack_main_default_stack_layout,
+                main_position: stack_main_default_main_position,
+            },
+            spiral_settings,
             rename_handle: None,
+            rename_delay: Duration::from_millis(workspace_renaming_delay_ms),
+            layout_ignore,
+            events,
+            dry_run,
+            focus_history: Vec::with_capacity(FOCUS_HISTORY_LEN),
+            smart_gaps,
+            gapless: HashMap::new(),
+            relayout_tx: Self::spawn_relayout_handler(
+                Duration::from_millis(relayout_step_delay_ms),
+                dry_run,
+            ),
+            pause_modes: pause_in_mode,
+            paused_in_mode: None,
+            enabled: true,
+            paused_hooks: false,
+            last_event_at: None,
+            managed_workspaces,
+            unmanaged_workspaces,
+            unmanaged_focus_hooks,
+            ignore_transient,
+            undo: None,
+            stash: Vec::new(),
+            pinned: Vec::new(),
+            on_workspace_empty,
+            emptied: HashSet::new(),
         })
     }
 
-    /// Return a mutable reference to the configuration of workspace `ws_num`.
+    /// Lay out again the workspaces whose windows no longer have the shape of their layout.
+    ///
+    /// Only `stack_main` workspaces are checked, the other layouts have no fixed shape.
+    /// Does nothing while layouts are paused (by `pause` or a mode) or window events came in within
+    /// `RECONCILE_QUIET_PERIOD`, and skips workspaces with fullscreen or ignored windows,
+    /// which are left where they are on purpose.
+    pub async fn reconcile(&mut self) -> Result<()> {
+        if !self.enabled
+            || self.paused_in_mode.is_some()
+            || self
+                .last_event_at
+                .is_some_and(|at| at.elapsed() < RECONCILE_QUIET_PERIOD)
+        {
+            return Ok(());
+        }
+        let tree = self.connection.get_tree().await?;
+        let mut relayout = Vec::new();
+        for ws in tree.iter().filter(|n| n.is_workspace()) {
+            if ws.nodes.is_empty() || utils::is_special_workspace_node(ws) {
+                continue;
+            }
+            let key = WorkspaceKey::of_node(ws);
+            if !self.is_managed(&key) {
+                continue;
+            }
+            let Some(config) = self.workspace_config.get(&key) else {
+                continue;
+            };
+            if !matches!(config.layout, WorkspaceLayout::StackMain { .. })
+                || ws.iter().any(|n| {
+                    n.is_full_screen()
+                        || n.matches_app(&self.layout_ignore)
+                        || (self.ignore_transient && n.is_transient())
+                })
+                || StackMain::has_shape(ws)
+            {
+                continue;
+            }
+            log::info!("workspace {key} lost its {} shape, relayout", config.layout);
+            relayout.push(key);
+        }
+        for key in relayout {
+            self.relayout(key);
+        }
+        Ok(())
+    }
+
+    /// Replace every connection to Sway after the previous ones were lost, eg. when
+    /// sway restarted.
+    ///
+    /// The layout handlers are spawned again with fresh connections, the old ones
+    /// stop once their senders are dropped. Workspace layouts are kept.
+    pub async fn reconnect(&mut self) -> Result<()> {
+        self.connection = Connection::new().await?;
+        self.window_focus_handler.reconnect().await?;
+        self.spiral_tx = Spiral::spawn_handler(
+            self.layout_ignore.clone(),
+            self.spiral_settings,
+            self.dry_run,
+        );
+        self.dwindle_tx = Dwindle::spawn_handler(self.layout_ignore.clone(), self.dry_run);
+        self.stack_main_tx = StackMain::spawn_handler(
+            self.layout_ignore.clone(),
+            self.stack_main_settings,
+            self.dry_run,
+        );
+        Ok(())
+    }
+
+    /// The first `--workspace-layout-rule` layout whose regex matches the workspace `name`.
+    fn workspace_rule_layout(&self, name: &str) -> Option<&WorkspaceLayout> {
+        self.workspace_layout_rules
+            .iter()
+            .find(|(regex, _)| regex.is_match(name))
+            .map(|(_, layout)| layout)
+    }
+
+    /// Return a mutable reference to the configuration of workspace `key`.
     ///
-    /// If no config exists for `ws_num`, a new entry is inserted with `self.default_layout`.
-    pub fn get_workspace_config(&mut self, ws_num: i32) -> &WorkspaceConfig {
+    /// If no config exists for `key`, a new entry is inserted with the layout of the
+    /// first workspace rule matching `name`, else the default layout of `output` (the
+    /// workspace's output), or `self.default_layout` if it has none.
+    pub fn get_workspace_config(
+        &mut self,
+        key: WorkspaceKey,
+        name: &str,
+        output: &str,
+    ) -> &mut WorkspaceConfig {
+        let layout = self
+            .workspace_rule_layout(name)
+            .or_else(|| self.output_layouts.get(output))
+            .unwrap_or(&self.default_layout)
+            .clone();
         self.workspace_config
-            .entry(ws_num)
+            .entry(key)
             .or_insert_with(|| WorkspaceConfig {
-                layout: self.default_layout.clone(),
+                layout,
+                seeded: true,
+                previous_layout: None,
+                from_app_rule: false,
+                zoomed_from: None,
+                spiral_dir: SplitDir::Auto,
+                focus_hooks: FocusHooks::default(),
             })
     }
 
     /// Handle a Sway `WindowEvent` by:
     /// 1. Debouncing workspace renaming (if enabled).
-    /// 2. Routing the event to the appropriate layout handler (`spiral` or `stack_main`).
-    /// 3. Passing the event to the `WindowFocus` handler for opacity/mark effects.
+    /// 2. Routing the event to the appropriate layout handler (`spiral`, `dwindle`, `stack_main`
+    ///    or `centered_main`).
+    /// 3. Adjusting the gaps of the focused workspace (if `--smart-gaps` is set).
+    /// 4. Passing the event to the `WindowFocus` handler for opacity/mark effects.
+    ///
+    /// The Sway tree is fetched at most once per event, only if the workspace's
+    /// layout handler or smart gaps act on this kind of event, and shared with both.
+    ///
+    /// On workspaces that aren't managed only step 4 runs, and only with
+    /// `--unmanaged-focus-hooks`. Events of transient windows skip steps 1 and 2 with
+    /// `--ignore-transient`.
     ///
     /// This method is called from the `Daemon`’s event loop for every `Window` event.
     pub async fn handle_event(&mut self, event: Box<WindowEvent>) -> Result<()> {
         log::debug!("controller.handle_event: {:?}", event.change);
+        self.last_event_at = Some(Instant::now());
+
+        if !self.enabled && self.paused_hooks {
+            log::debug!("paused, only recording focus");
+            self.handle_focus(event, None).await;
+            return Ok(());
+        }
 
         let ws = utils::get_focused_workspace(&mut self.connection).await?;
+        let key = WorkspaceKey::of(&ws);
+        match event.change {
+            WindowChange::Close | WindowChange::Move => self.check_workspace_empty(&key).await?,
+            WindowChange::New | WindowChange::Focus => {
+                self.emptied.remove(&key);
+            }
+            _ => {}
+        }
+        if !self.is_managed(&key) {
+            log::debug!("workspace {key} is not managed, skipping layout and renaming");
+            let hooks = self
+                .unmanaged_focus_hooks
+                .then(|| self.focus_hooks_of(&key));
+            self.handle_focus(event, hooks).await;
+            return Ok(());
+        }
+
+        let transient = self.ignore_transient && event.container.is_transient();
 
         // --- 1. DEBOUNCED RENAMING ---
-        if self.workspace_renaming {
+        if self.workspace_renaming && !transient {
             // Cancel the previous pending rename task if it exists
             if let Some(handle) = self.rename_handle.take() {
                 handle.abort();
             }
 
             let event_clone = event.clone();
+            let events = self.events.clone();
+            let config = Arc::clone(&self.renamer_config);
+            let delay = self.rename_delay;
 
             // Spawn a new task with a delay
             self.rename_handle = Some(task::spawn(async move {
-                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                event_handlers::misc::workspace_renamer::WorkspaceRenamer::handle(event_clone)
-                    .await;
+                tokio::time::sleep(delay).await;
+                event_handlers::misc::workspace_renamer::WorkspaceRenamer::handle(
+                    event_clone,
+                    events,
+                    config,
+                )
+                .await;
             }));
         }
 
         // --- 2. LAYOUT MANAGEMENT ---
-        match &self.get_workspace_config(ws.num).layout {
+        let layout = if !self.enabled {
+            log::debug!("layouts paused");
+            WorkspaceLayout::Manual
+        } else if let Some(mode) = &self.paused_in_mode {
+            log::debug!("layouts paused in mode {mode}");
+            WorkspaceLayout::Manual
+        } else if transient {
+            log::debug!("skip layout of transient window {}", event.container.id);
+            WorkspaceLayout::Manual
+        } else if event.change == WindowChange::New
+            && self.apply_app_layout(&key, &ws, &event.container)
+        {
+            // The relayout places the new window as well, keep the handlers out of it
+            WorkspaceLayout::Manual
+        } else {
+            self.get_workspace_config(key.clone(), &ws.name, &ws.output)
+                .layout
+                .clone()
+        };
+        let needs_tree = match &layout {
+            WorkspaceLayout::Spiral => Spiral::needs_tree(event.change),
+            WorkspaceLayout::Dwindle => Dwindle::needs_tree(event.change),
+            WorkspaceLayout::StackMain { .. } => StackMain::needs_tree(event.change),
+            WorkspaceLayout::CenteredMain { .. } => CenteredMain::needs_tree(event.change),
+            WorkspaceLayout::Manual => false,
+        };
+        let tree = if needs_tree
+            || (self.enabled && self.smart_gaps.is_some() && needs_gaps(event.change))
+        {
+            Some(Arc::new(self.connection.get_tree().await?))
+        } else {
+            None
+        };
+
+        match layout {
             WorkspaceLayout::Spiral => {
                 log::debug!("handling event via spiral manager");
-                if let Err(e) = self.spiral_tx.send(event.clone()) {
+                let split_dir = self
+                    .workspace_config
+                    .get(&key)
+                    .map_or(SplitDir::Auto, |config| config.spiral_dir);
+                if let Err(e) = self
+                    .spiral_tx
+                    .send((event.clone(), tree.clone(), split_dir))
+                {
                     log::error!("failed to send event to spiral handler: {e}");
                 }
             }
-            WorkspaceLayout::StackMain { stack_layout, size } => {
+            WorkspaceLayout::Dwindle => {
+                log::debug!("handling event via dwindle manager");
+                if let Err(e) = self.dwindle_tx.send((event.clone(), tree.clone())) {
+                    log::error!("failed to send event to dwindle handler: {e}");
+                }
+            }
+            WorkspaceLayout::StackMain {
+                stack_layout,
+                size,
+                main_position,
+            } => {
                 log::debug!("handling event via stack_main manager");
-                task::spawn(event_handlers::layout::stack_main::StackMain::handle(
+                if let Err(e) = self.stack_main_tx.send((
                     event.clone(),
-                    *size,
-                    stack_layout.clone(),
+                    tree.clone(),
+                    size,
+                    stack_layout,
+                    main_position,
+                )) {
+                    log::error!("failed to send event to stack_main handler: {e}");
+                }
+            }
+            WorkspaceLayout::CenteredMain { size } => {
+                log::debug!("handling event via centered_main manager");
+                task::spawn(CenteredMain::handle(
+                    event.clone(),
+                    tree.clone(),
+                    size,
+                    self.layout_ignore.clone(),
+                    self.dry_run,
                 ));
             }
             WorkspaceLayout::Manual => {}
         }
 
-        // --- 3. FOCUS HANDLER ---
-        self.window_focus_handler.handle(event).await;
+        // --- 3. SMART GAPS ---
+        if self.enabled
+            && let (Some(gaps), Some(tree)) = (self.smart_gaps, tree)
+        {
+            self.apply_smart_gaps(&ws, &tree, gaps).await?;
+        }
+
+        // --- 4. FOCUS HANDLER ---
+        let hooks = self.focus_hooks_of(&key);
+        self.handle_focus(event, Some(hooks)).await;
+
+        Ok(())
+    }
+
+    /// Run `--on-workspace-empty` if workspace `key` has just lost its last window.
+    ///
+    /// Runs once per workspace until it gets a window again, the special workspaces
+    /// are left out.
+    async fn check_workspace_empty(&mut self, key: &WorkspaceKey) -> Result<()> {
+        let Some(cmd) = self.on_workspace_empty.clone() else {
+            return Ok(());
+        };
+        if self.emptied.contains(key) {
+            return Ok(());
+        }
+        let tree = self.connection.get_tree().await?;
+        let Some(ws) = tree.find_as_ref(|n| key.matches(n)) else {
+            return Ok(());
+        };
+        if utils::is_special_workspace_node(ws)
+            || ws.iter().any(|n| n.is_window() || n.is_floating_window())
+        {
+            return Ok(());
+        }
+        log::debug!("workspace {key} is empty, running: {cmd}");
+        self.emptied.insert(key.clone());
+        utils::run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Whether persway lays out and renames workspace `key`.
+    fn is_managed(&self, key: &WorkspaceKey) -> bool {
+        (self.managed_workspaces.is_empty() || self.managed_workspaces.contains(key))
+            && !self.unmanaged_workspaces.contains(key)
+    }
+
+    /// Record focus changes for `focus-back` and, with `hooks`, pass the event to the
+    /// `WindowFocus` handler.
+    async fn handle_focus(&mut self, event: Box<WindowEvent>, hooks: Option<FocusHooks>) {
+        if event.change == WindowChange::Focus {
+            self.remember_focus(event.container.id);
+        }
+        if let Some(hooks) = hooks {
+            self.window_focus_handler
+                .handle_with_hooks(event, &hooks)
+                .await;
+        }
+    }
+
+    /// The focus commands set on workspace `key` with `set-focus-hooks`.
+    fn focus_hooks_of(&self, key: &WorkspaceKey) -> FocusHooks {
+        self.workspace_config
+            .get(key)
+            .map(|config| config.focus_hooks.clone())
+            .unwrap_or_default()
+    }
+
+    /// Switch workspace `key` to the layout of the first `--app-layout` rule matching
+    /// the new window `node` and lay it out again. Returns whether it did.
+    ///
+    /// A layout set with a command is never overridden, one set by another rule is.
+    fn apply_app_layout(&mut self, key: &WorkspaceKey, ws: &Workspace, node: &Node) -> bool {
+        let Some(app) = node.app_id_or_class() else {
+            return false;
+        };
+        let Some(layout) = self
+            .app_layouts
+            .iter()
+            .find(|(pattern, _)| app_pattern_matches(pattern, app))
+            .map(|(_, layout)| layout.clone())
+        else {
+            return false;
+        };
+        let config = self.get_workspace_config(key.clone(), &ws.name, &ws.output);
+        if config.layout == layout {
+            return false;
+        }
+        if !config.seeded && !config.from_app_rule {
+            log::debug!("app layout: {app} keeps the layout set on ws {key}");
+            return false;
+        }
+        log::debug!("app layout: {app} switches ws {key} to {layout}");
+        self.set_layout(key.clone(), layout);
+        self.get_workspace_config(key.clone(), &ws.name, &ws.output)
+            .from_app_rule = true;
+        self.relayout(key.clone());
+        true
+    }
+
+    /// Drop the inner gaps of workspace `ws` when it has a single tiled window and
+    /// restore them to `gaps` otherwise.
+    ///
+    /// Only runs a command when that changes, persway's tmp workspace and the
+    /// scratchpad are left alone.
+    async fn apply_smart_gaps(&mut self, ws: &Workspace, tree: &Node, gaps: u32) -> Result<()> {
+        let key = WorkspaceKey::of(ws);
+        let Some(ws_node) = tree.find_as_ref(|n| key.matches(n)) else {
+            return Ok(());
+        };
+        if utils::is_special_workspace_node(ws_node) {
+            return Ok(());
+        }
+        let gapless = ws_node.iter().filter(|n| n.is_window()).count() == 1;
+        if self.gapless.get(&key) == Some(&gapless) {
+            return Ok(());
+        }
+        let px = if gapless { 0 } else { gaps };
+        utils::run_command(
+            &mut self.connection,
+            format!("gaps inner current set {px}"),
+            self.dry_run,
+        )
+        .await?;
+        self.gapless.insert(key, gapless);
+        Ok(())
+    }
+
+    /// Handle a Sway `BindingEvent`: publish it on the events socket and, when the
+    /// binding switches modes, pause the layouts in a `--pause-in-mode` mode or
+    /// resume them in any other.
+    pub fn handle_binding_event(&mut self, event: &BindingEvent) {
+        let command = &event.binding.command;
+        log::debug!("controller.handle_binding_event: {command}");
+        events::publish(
+            &self.events,
+            PerswayEvent::Binding {
+                command: command.clone(),
+            },
+        );
+        if let Some(mode) = binding_mode(command) {
+            if self.pause_modes.contains(&mode) {
+                log::debug!("pausing layouts in mode {mode}");
+                self.paused_in_mode = Some(mode);
+            } else if let Some(paused) = self.paused_in_mode.take() {
+                log::debug!("resuming layouts, left mode {paused}");
+            }
+        }
+    }
+
+    /// Push `id` onto the focus history, keeping each window in it only once.
+    fn remember_focus(&mut self, id: i64) {
+        self.focus_history.retain(|&prev| prev != id);
+        if self.focus_history.len() == FOCUS_HISTORY_LEN {
+            self.focus_history.remove(0);
+        }
+        self.focus_history.push(id);
+    }
+
+    /// Focus the most recently focused window other than the focused one, on any workspace.
+    ///
+    /// Windows that were closed since are dropped from the history first.
+    async fn focus_back(&mut self) -> Result<()> {
+        let tree = self.connection.get_tree().await?;
+        self.focus_history
+            .retain(|&id| tree.find_as_ref(|n| n.id == id).is_some());
+        let focused = tree.find_as_ref(|n| n.focused).map(|n| n.id);
+        let Some(&id) = self
+            .focus_history
+            .iter()
+            .rev()
+            .find(|&&id| Some(id) != focused)
+        else {
+            log::debug!("focus-back: no previously focused window");
+            return Ok(());
+        };
+        let cmd = format!("[con_id={id}] focus");
+        utils::run_command(&mut self.connection, cmd, self.dry_run).await
+    }
+
+    /// Give the existing stack of `stack_main` workspace `ws` the layout `stack_layout`
+    /// without moving any window, and keep it for the windows added later.
+    async fn set_stack_layout(
+        &mut self,
+        ws: &Workspace,
+        size: u8,
+        stack_layout: StackLayout,
+        main_position: MainPosition,
+    ) -> Result<()> {
+        let mut ctrl = command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+        ctrl.stack_set_layout(&stack_layout, main_position).await?;
+
+        let layout = WorkspaceLayout::StackMain {
+            size,
+            stack_layout,
+            main_position,
+        };
+        let key = WorkspaceKey::of(ws);
+        let config = self.get_workspace_config(key.clone(), &ws.name, &ws.output);
+        config.layout = layout.clone();
+        config.seeded = false;
+        events::publish(
+            &self.events,
+            PerswayEvent::LayoutChanged {
+                workspace: key,
+                layout,
+            },
+        );
+        Ok(())
+    }
+
+    /// Focus the most recently focused window of app `app_id` (or X11 class), or the
+    /// first one in the tree if none of them was focused lately, on any workspace.
+    ///
+    /// Returns the id of the window.
+    async fn focus_app(&mut self, app_id: &str) -> Result<i64> {
+        let tree = self.connection.get_tree().await?;
+        let windows = tree
+            .iter()
+            .filter(|n| n.is_window() || n.is_floating_window())
+            .filter(|n| {
+                n.app_id_or_class()
+                    .is_some_and(|app| app_pattern_matches(app_id, app))
+            })
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        let Some(&first) = windows.first() else {
+            bail!("no window of app {app_id}");
+        };
+        let id = self
+            .focus_history
+            .iter()
+            .rev()
+            .find(|id| windows.contains(id))
+            .copied()
+            .unwrap_or(first);
+        let cmd = format!("[con_id={id}] focus");
+        utils::run_command(&mut self.connection, cmd, self.dry_run).await?;
+        Ok(id)
+    }
+
+    /// The workspace renaming settings, shared so the icons can be reloaded on SIGHUP.
+    pub fn renamer_config(&self) -> Arc<RenamerConfig> {
+        Arc::clone(&self.renamer_config)
+    }
+
+    /// Describe the handler's settings and per-workspace layouts for `persway status`.
+    pub fn status(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("default layout: {}", self.default_layout),
+            format!(
+                "workspace renaming: {}",
+                if self.workspace_renaming { "on" } else { "off" }
+            ),
+            format!(
+                "on window focus: {}",
+                self.window_focus_handler.focus_cmd().unwrap_or("none")
+            ),
+            format!(
+                "on window focus leave: {}",
+                self.window_focus_handler
+                    .focus_leave_cmd()
+                    .unwrap_or("none")
+            ),
+        ];
+        if let Some(gaps) = self.smart_gaps {
+            lines.push(format!("smart gaps: {gaps}"));
+        }
+        let mut locked = self
+            .renamer_config
+            .locked
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        if !locked.is_empty() {
+            locked.sort();
+            lines.push(format!("locked workspace names: {}", locked.join(", ")));
+        }
+        if !self.enabled {
+            lines.push(format!(
+                "paused: {}",
+                if self.paused_hooks {
+                    "everything"
+                } else {
+                    "layouts"
+                }
+            ));
+        }
+        if let Some(mode) = &self.paused_in_mode {
+            lines.push(format!("layouts paused in mode: {mode}"));
+        }
+        let list = |keys: &[WorkspaceKey]| {
+            keys.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if !self.managed_workspaces.is_empty() {
+            lines.push(format!(
+                "managed workspaces: {}",
+                list(&self.managed_workspaces)
+            ));
+        }
+        if !self.unmanaged_workspaces.is_empty() {
+            lines.push(format!(
+                "unmanaged workspaces: {}",
+                list(&self.unmanaged_workspaces)
+            ));
+        }
+        if self.dry_run {
+            lines.push(String::from("dry run: on"));
+        }
+        self.layout_defaults(&mut lines);
+        let mut workspaces = self.workspace_config.iter().collect::<Vec<_>>();
+        workspaces.sort_by_key(|(key, _)| *key);
+        for (key, config) in workspaces {
+            if config.spiral_dir == SplitDir::Auto {
+                lines.push(format!("workspace {key}: {}", config.layout));
+            } else {
+                lines.push(format!(
+                    "workspace {key}: {} (split {})",
+                    config.layout, config.spiral_dir
+                ));
+            }
+        }
+        lines
+    }
+
+    /// Push the default layouts per output, workspace rule and app and the presets,
+    /// for `status` and `dump-config`.
+    fn layout_defaults(&self, lines: &mut Vec<String>) {
+        let mut outputs = self.output_layouts.iter().collect::<Vec<_>>();
+        outputs.sort_by_key(|(output, _)| *output);
+        for (output, layout) in outputs {
+            lines.push(format!("default layout on {output}: {layout}"));
+        }
+        for (regex, layout) in &self.workspace_layout_rules {
+            lines.push(format!("layout for workspaces matching {regex}: {layout}"));
+        }
+        for (app, layout) in &self.app_layouts {
+            lines.push(format!("layout for {app}: {layout}"));
+        }
+        let mut presets = self.presets.iter().collect::<Vec<_>>();
+        presets.sort_by_key(|(name, _)| *name);
+        for (name, layout) in presets {
+            lines.push(format!("preset {name}: {layout}"));
+        }
+    }
+
+    /// Lines of `persway dump-config` with the settings the handler was created with.
+    pub fn dump_config(&self) -> Vec<String> {
+        let or_none = |cmd: Option<&str>| cmd.unwrap_or("none").to_string();
+        let list = |items: Vec<String>| {
+            if items.is_empty() {
+                String::from("none")
+            } else {
+                items.join(", ")
+            }
+        };
+        let mut lines = vec![
+            format!("default layout: {}", self.default_layout),
+            format!(
+                "workspace renaming: {}",
+                if self.workspace_renaming { "on" } else { "off" }
+            ),
+            format!(
+                "workspace renaming delay: {}ms",
+                self.rename_delay.as_millis()
+            ),
+            format!("workspace name format: {}", self.renamer_config.format),
+            format!(
+                "on window focus: {}",
+                or_none(self.window_focus_handler.focus_cmd())
+            ),
+            format!(
+                "on window focus leave: {}",
+                or_none(self.window_focus_handler.focus_leave_cmd())
+            ),
+            format!(
+                "on workspace empty: {}",
+                or_none(self.on_workspace_empty.as_deref())
+            ),
+            format!("layout ignore: {}", list(self.layout_ignore.clone())),
+            format!("pause in modes: {}", list(self.pause_modes.clone())),
+            format!(
+                "managed workspaces: {}",
+                list(
+                    self.managed_workspaces
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect()
+                )
+            ),
+            format!(
+                "unmanaged workspaces: {}",
+                list(
+                    self.unmanaged_workspaces
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect()
+                )
+            ),
+            format!("unmanaged focus hooks: {}", self.unmanaged_focus_hooks),
+            format!(
+                "smart gaps: {}",
+                self.smart_gaps
+                    .map_or_else(|| String::from("off"), |gaps| gaps.to_string())
+            ),
+            format!("ignore transient: {}", self.ignore_transient),
+            format!("dry run: {}", self.dry_run),
+            format!("stack main default: {}", self.stack_main_default),
+            format!(
+                "stack insert: {}",
+                self.stack_main_settings.insert.map_or_else(
+                    || String::from("next to last focused"),
+                    |insert| insert.to_string()
+                )
+            ),
+            format!(
+                "stack main min main: {}",
+                self.stack_main_settings
+                    .min_main_px
+                    .map_or_else(|| String::from("none"), |px| format!("{px}px"))
+            ),
+            format!(
+                "gap aware resize: {}",
+                self.stack_main_settings.gap_aware_resize
+            ),
+            format!(
+                "spiral ratio: {}",
+                self.spiral_settings
+                    .ratio
+                    .map_or_else(|| String::from("equal"), |ratio| ratio.to_string())
+            ),
+            format!("spiral hysteresis: {}", self.spiral_settings.hysteresis),
+            format!(
+                "spiral throttle: {}ms",
+                self.spiral_settings.throttle.as_millis()
+            ),
+            format!(
+                "spiral resize cooldown: {}",
+                self.spiral_settings
+                    .resize_cooldown
+                    .map_or_else(|| String::from("none"), |d| format!("{}ms", d.as_millis()))
+            ),
+        ];
+        self.layout_defaults(&mut lines);
+        lines
+    }
+
+    /// Switch workspace `key` from `current_layout` to `layout` and lay it out again.
+    fn change_layout(
+        &mut self,
+        key: WorkspaceKey,
+        current_layout: &WorkspaceLayout,
+        layout: WorkspaceLayout,
+    ) {
+        if *current_layout == layout {
+            // Optional: return Ok(()) or print a message; no need to error
+            log::debug!("layout already set for ws {key}");
+            return;
+        }
+        self.set_layout(key.clone(), layout);
+        self.relayout(key);
+    }
+
+    /// Record `layout` as the layout of workspace `key` without touching its windows.
+    ///
+    /// A layout other than `manual` that gets replaced is remembered for `toggle-manual`.
+    fn set_layout(&mut self, key: WorkspaceKey, layout: WorkspaceLayout) {
+        let config = self
+            .workspace_config
+            .entry(key.clone())
+            .or_insert_with(|| WorkspaceConfig {
+                layout: layout.clone(),
+                seeded: false,
+                previous_layout: None,
+                from_app_rule: false,
+                zoomed_from: None,
+                spiral_dir: SplitDir::Auto,
+                focus_hooks: FocusHooks::default(),
+            });
+        if config.layout != WorkspaceLayout::Manual {
+            config.previous_layout = Some(mem::replace(&mut config.layout, layout.clone()));
+        } else {
+            config.layout = layout.clone();
+        }
+        config.seeded = false;
+        config.from_app_rule = false;
+        config.zoomed_from = None;
+
+        events::publish(
+            &self.events,
+            PerswayEvent::LayoutChanged {
+                workspace: key,
+                layout,
+            },
+        );
+    }
+
+    /// Lay workspace `key` out again from scratch.
+    ///
+    /// The workspace is moved out of the way and its windows are moved back one
+    /// by one, so the layout handler of the workspace places each of them as if
+    /// it were new.
+    ///
+    /// Relayouts run one at a time, see `spawn_relayout_handler`.
+    fn relayout(&self, key: WorkspaceKey) {
+        if let Err(e) = self.relayout_tx.send(RelayoutJob::Relayout(key)) {
+            log::error!("failed to send relayout to the relayout handler: {e}");
+        }
+    }
+
+    /// Spawn the task that runs relayouts and snapshot restores one after the other,
+    /// they all pass through the same tmp workspace.
+    ///
+    /// A relayout of a workspace that is asked for again while the first one still
+    /// waits its turn runs only once, eg. when `cycle-layout` is pressed repeatedly.
+    /// It lays the workspace out by its layout at the time it runs.
+    fn spawn_relayout_handler(
+        step_delay: Duration,
+        dry_run: bool,
+    ) -> mpsc::UnboundedSender<RelayoutJob> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        task::spawn(async move {
+            while let Some(job) = rx.recv().await {
+                let mut jobs = vec![job];
+                while let Ok(job) = rx.try_recv() {
+                    if let RelayoutJob::Relayout(key) = &job
+                        && jobs
+                            .iter()
+                            .any(|queued| matches!(queued, RelayoutJob::Relayout(k) if k == key))
+                    {
+                        log::debug!("relayout of workspace {key} already queued, dropping");
+                        continue;
+                    }
+                    jobs.push(job);
+                }
+                for job in jobs {
+                    match job {
+                        RelayoutJob::Relayout(key) => {
+                            Self::relayout_workspace(key, step_delay, dry_run).await;
+                        }
+                        RelayoutJob::Restore {
+                            key,
+                            windows,
+                            resize,
+                        } => {
+                            Self::restore_workspace(key, windows, resize, step_delay, dry_run)
+                                .await;
+                        }
+                        RelayoutJob::Swap { a, b } => {
+                            Self::swap_workspaces(a, b, step_delay, dry_run).await;
+                        }
+                    }
+                }
+            }
+        });
+
+        tx
+    }
+
+    /// Move the `windows` of a snapshot onto workspace `key` in their saved order, after
+    /// the windows already on it, like a relayout does.
+    ///
+    /// With `resize` the windows get their saved size back, for layouts that don't size
+    /// windows themselves.
+    async fn restore_workspace(
+        key: WorkspaceKey,
+        windows: Vec<SnapshotWindow>,
+        resize: bool,
+        step_delay: Duration,
+        dry_run: bool,
+    ) {
+        let res = utils::relayout_workspace(
+            key.clone(),
+            dry_run,
+            move |mut conn, ws_target, _old_ws_id, _output_id, current| async move {
+                let others = current
+                    .iter()
+                    .map(|n| n.id)
+                    .filter(|id| windows.iter().all(|w| w.id != *id));
+                let ids = others
+                    .chain(windows.iter().map(|w| w.id))
+                    .collect::<Vec<_>>();
+                for id in ids {
+                    let cmd =
+                        format!("[con_id={id}] move to workspace {ws_target}; [con_id={id}] focus");
+                    utils::run_command(&mut conn, cmd, dry_run).await?;
+                    tokio::time::sleep(step_delay).await;
+                }
+                if resize {
+                    for window in &windows {
+                        let cmd = format!(
+                            "[con_id={}] resize set width {} px height {} px",
+                            window.id, window.width, window.height
+                        );
+                        utils::run_command(&mut conn, cmd, dry_run).await?;
+                    }
+                }
+                Ok(())
+            },
+        )
+        .await;
+        if let Err(e) = res {
+            log::error!("restore of workspace {key} failed: {e}");
+        }
+    }
+
+    /// Exchange the windows of workspaces `a` and `b` through the tmp workspace, then
+    /// lay both out again by their own layouts.
+    ///
+    /// The windows of `a` are parked on the tmp workspace while the ones of `b` move to
+    /// `a`, and the workspace that had focus gets it back.
+    async fn swap_workspaces(
+        a: WorkspaceKey,
+        b: WorkspaceKey,
+        step_delay: Duration,
+        dry_run: bool,
+    ) {
+        let swap = async {
+            let mut conn = Connection::new().await?;
+            let tree = conn.get_tree().await?;
+            let windows_of = |key: &WorkspaceKey| -> Result<(String, Vec<i64>)> {
+                let ws = tree
+                    .find_as_ref(|n| key.matches(n))
+                    .context(format!("no workspace {key}"))?;
+                let target = utils::workspace_target(
+                    ws.num.unwrap_or(-1),
+                    ws.name.as_deref().context("workspace has no name")?,
+                );
+                let windows = ws
+                    .iter()
+                    .filter(|n| n.is_window() || n.is_floating_window())
+                    .map(|n| n.id)
+                    .collect();
+                Ok((target, windows))
+            };
+            let (a_target, a_windows) = windows_of(&a)?;
+            let (b_target, b_windows) = windows_of(&b)?;
+            let focused = conn
+                .get_workspaces()
+                .await?
+                .into_iter()
+                .find(|w| w.focused)
+                .context("no focused workspace")?;
+            let moves = [
+                (&a_windows, format!("\"{}\"", utils::PERSWAY_TMP_WORKSPACE)),
+                (&b_windows, a_target),
+                (&a_windows, b_target),
+            ];
+            for (windows, target) in moves {
+                for id in windows {
+                    let cmd = format!("[con_id={id}] move to workspace {target}");
+                    utils::run_command(&mut conn, cmd, dry_run).await?;
+                    tokio::time::sleep(step_delay).await;
+                }
+            }
+            let cmd = format!(
+                "workspace {}",
+                utils::workspace_target(focused.num, &focused.name)
+            );
+            utils::run_command(&mut conn, cmd, dry_run).await
+        };
+        if let Err(e) = swap.await {
+            log::error!("swap of workspaces {a} and {b} failed: {e}");
+            return;
+        }
+        Self::relayout_workspace(a, step_delay, dry_run).await;
+        Self::relayout_workspace(b, step_delay, dry_run).await;
+    }
+
+    /// Relayout workspace `key` through the tmp workspace.
+    ///
+    /// Sway gets `step_delay` to settle after each window is moved back.
+    async fn relayout_workspace(key: WorkspaceKey, step_delay: Duration, dry_run: bool) {
+        let res = utils::relayout_workspace(
+            key.clone(),
+            dry_run,
+            move |mut conn, ws_target, _old_ws_id, _output_id, windows| async move {
+                for window in windows.iter().rev() {
+                    let cmd = format!(
+                        "[con_id={}] move to workspace {}; [con_id={}] focus",
+                        window.id, ws_target, window.id
+                    );
+                    utils::run_command(&mut conn, cmd, dry_run).await?;
+                    tokio::time::sleep(step_delay).await;
+                }
+                Ok(())
+            },
+        )
+        .await;
+        if let Err(e) = res {
+            log::error!("relayout of workspace {key} failed: {e}");
+        }
+    }
+
+    /// Move every tiled window of workspace `from` to workspace `to`, then lay `to`
+    /// out again so its configured layout arranges them.
+    ///
+    /// Merging a workspace into itself, or an empty workspace, does nothing.
+    async fn merge_workspace(&mut self, from: WorkspaceKey, to: WorkspaceKey) -> Result<()> {
+        if from == to {
+            log::debug!("merge-workspace: {from} is the target, nothing to do");
+            return Ok(());
+        }
+        let tree = self.connection.get_tree().await?;
+        let source = tree
+            .find_as_ref(|n| from.matches(n))
+            .context(format!("no workspace {from}"))?;
+        let windows = source
+            .iter()
+            .filter(|n| n.is_window())
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        if windows.is_empty() {
+            log::debug!("merge-workspace: {from} has no tiled windows");
+            return Ok(());
+        }
+        let ws_target = match (&to, tree.find_as_ref(|n| to.matches(n))) {
+            (_, Some(target)) => utils::workspace_target(
+                target.num.unwrap_or(-1),
+                target.name.as_deref().unwrap_or_default(),
+            ),
+            (WorkspaceKey::Num(num), None) => format!("number {num}"),
+            (WorkspaceKey::Name(name), None) => format!("\"{name}\""),
+        };
+        let cmd = windows
+            .iter()
+            .map(|id| format!("[con_id={id}] move to workspace {ws_target}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        utils::run_command(&mut self.connection, cmd, self.dry_run).await?;
+        self.relayout(to);
+        Ok(())
+    }
 
+    /// Re-seed layouts after outputs were connected or disconnected.
+    ///
+    /// Every workspace on an output with an `--output-layout` whose layout came from
+    /// a default (not from a command) gets the output's layout, and is relaid out if
+    /// that changed its layout and it has windows. Workspaces matching a
+    /// `--workspace-layout-rule` keep the layout of the rule.
+    ///
+    /// Docking relays out workspaces on outputs other than the focused one, the
+    /// focused workspace stays focused on the output it was on, see
+    /// `utils::relayout_workspace`.
+    pub async fn handle_output_event(&mut self) -> Result<()> {
+        let workspaces = self.connection.get_workspaces().await?;
+        let mut relayout = Vec::new();
+        for ws in workspaces {
+            let Some(layout) = self.output_layouts.get(&ws.output) else {
+                continue;
+            };
+            let key = WorkspaceKey::of(&ws);
+            if !self.is_managed(&key) || self.workspace_rule_layout(&ws.name).is_some() {
+                continue;
+            }
+            let config =
+                self.workspace_config
+                    .entry(key.clone())
+                    .or_insert_with(|| WorkspaceConfig {
+                        layout: layout.clone(),
+                        seeded: true,
+                        previous_layout: None,
+                        from_app_rule: false,
+                        zoomed_from: None,
+                        spiral_dir: SplitDir::Auto,
+                        focus_hooks: FocusHooks::default(),
+                    });
+            if !config.seeded || config.layout == *layout {
+                continue;
+            }
+            log::debug!("output {}: workspace {key} now uses {layout}", ws.output);
+            config.layout = layout.clone();
+            events::publish(
+                &self.events,
+                PerswayEvent::LayoutChanged {
+                    workspace: key.clone(),
+                    layout: layout.clone(),
+                },
+            );
+            if !ws.focus.is_empty() {
+                relayout.push(key);
+            }
+        }
+        if self.enabled {
+            for key in relayout {
+                self.relayout(key);
+            }
+        }
         Ok(())
     }
 
     fn require_stack_main(
-        ws_num: i32,
+        ws_key: &WorkspaceKey,
         ws_name: &str,
         layout: &WorkspaceLayout,
-        cmd: &str,
+        cmd: &'static str,
     ) -> Result<()> {
         ensure!(
             matches!(layout, WorkspaceLayout::StackMain { .. }),
-            "{cmd} only works on stack-main workspaces.\n\
-             Focused workspace: {ws_num} ('{ws_name}')\n\
-             Current layout: {layout:?}\n\
-             Fix: persway change-layout stack-main"
+            CommandError::WrongLayout {
+                cmd,
+                workspace: format!("{ws_key} ('{ws_name}')"),
+                layout: layout.clone(),
+                required: "stack-main",
+            }
         );
         Ok(())
     }
+
+    /// Fail `cmd` unless the stack of `stack_main` workspace `ws_key` is tiled, the
+    /// windows of tabbed and stacked stacks have no size of their own.
+    fn require_tiled_stack(
+        ws_key: &WorkspaceKey,
+        layout: &WorkspaceLayout,
+        cmd: &'static str,
+    ) -> Result<()> {
+        if let WorkspaceLayout::StackMain { stack_layout, .. } = layout {
+            ensure!(
+                *stack_layout == StackLayout::Tiled,
+                "{cmd} only works on a tiled stack, the stack of workspace {ws_key} is \
+                 {stack_layout}.\nFix: persway stack-set-layout tiled"
+            );
+        }
+        Ok(())
+    }
+
+    /// Grow (or `shrink`) the focused stack window of `stack_main` workspace `ws` by
+    /// `amount` percent, for `stack-grow`/`stack-shrink` (`cmd`).
+    async fn stack_resize(
+        &self,
+        ws: &Workspace,
+        layout: &WorkspaceLayout,
+        shrink: bool,
+        amount: u8,
+        cmd: &'static str,
+    ) -> Result<()> {
+        let ws_key = WorkspaceKey::of(ws);
+        Self::require_stack_main(&ws_key, &ws.name, layout, cmd)?;
+        Self::require_tiled_stack(&ws_key, layout, cmd)?;
+        let mut ctrl = command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+        ctrl.stack_resize(shrink, amount).await
+    }
+
     /// Handle a `PerswayCommand` such as layout changes or stack commands.
     ///
     /// # Arguments
@@ -170,98 +1683,715 @@ impl MessageHandler {
     /// - Fetches the focused workspace.
     /// - Updates layout state for that workspace if needed.
     /// - Executes the corresponding layout logic asynchronously (e.g., `relayout_workspace`).
-    pub async fn handle_command(&mut self, cmd: PerswayCommand) -> Result<()> {
+    ///
+    /// Returns the data to reply with, for commands that have any (e.g., `ListMarks`).
+    pub async fn handle_command(&mut self, cmd: PerswayCommand) -> Result<Option<String>> {
         log::debug!("controller.handle_command: {cmd:?}");
-        let ws = utils::get_focused_workspace(&mut self.connection).await?;
-
-        if ws.num < 0 {
-            bail!(
-                "Focused workspace '{}' has no numeric workspace number, so persway commands that key off ws.num won't apply. \
-Consider naming workspaces with a leading number (e.g. '1: web').",
-                ws.name
-            );
-        }
+        let ws = match cmd.workspace() {
+            Some(key) => self
+                .connection
+                .get_workspaces()
+                .await?
+                .into_iter()
+                .find(|w| WorkspaceKey::of(w) == *key)
+                .with_context(|| format!("no workspace {key}"))?,
+            None => utils::get_focused_workspace(&mut self.connection).await?,
+        };
+        let ws_key = WorkspaceKey::of(&ws);
 
         // Snapshot current layout so we don't keep borrowing self.workspace_config
-        let current_layout = self.get_workspace_config(ws.num).layout.clone();
+        let current_layout = self
+            .get_workspace_config(ws_key.clone(), &ws.name, &ws.output)
+            .layout
+            .clone();
 
-        match cmd {
-            PerswayCommand::ChangeLayout { layout } => {
-                if current_layout == layout {
-                    // Optional: return Ok(()) or print a message; no need to error
-                    log::debug!("layout already set for ws {}", ws.num);
-                    return Ok(());
-                }
+        // Whatever else changes windows or layouts makes the recorded undo stale.
+        if !matches!(
+            cmd,
+            PerswayCommand::Undo
+                | PerswayCommand::StackFocusNext
+                | PerswayCommand::StackFocusPrev
+                | PerswayCommand::FocusBack
+                | PerswayCommand::FocusApp { .. }
+                | PerswayCommand::FocusSpatial { .. }
+                | PerswayCommand::FocusedWindow
+                | PerswayCommand::ListWindows
+                | PerswayCommand::ListMarks
+                | PerswayCommand::Pause { .. }
+                | PerswayCommand::Resume
+                | PerswayCommand::LockName { .. }
+                | PerswayCommand::UnlockName
+                | PerswayCommand::SaveSnapshot { .. }
+        ) {
+            self.undo = None;
+        }
 
-                self.workspace_config
-                    .entry(ws.num)
-                    .and_modify(|e| e.layout = layout.clone())
-                    .or_insert_with(|| WorkspaceConfig {
-                        layout: layout.clone(),
+        match cmd {
+            PerswayCommand::ChangeLayout { layout, .. } => {
+                layout.validate()?;
+                if layout != current_layout {
+                    self.undo = Some(UndoAction::Layout {
+                        workspace: ws_key.clone(),
+                        layout: current_layout.clone(),
                     });
+                }
+                self.change_layout(ws_key, &current_layout, layout);
+            }
 
-                task::spawn(utils::relayout_workspace(
-                    ws.num,
-                    |mut conn, ws_num, _old_ws_id, _output_id, windows| async move {
-                        for window in windows.iter().rev() {
-                            let cmd = format!(
-                                "[con_id={}] move to workspace number {}; [con_id={}] focus",
-                                window.id, ws_num, window.id
+            PerswayCommand::Undo => {
+                let Some(action) = self.undo.take() else {
+                    bail!(
+                        "nothing to undo, only {UNDOABLE_COMMANDS} can be undone, \
+                         and only until another command changes the workspace"
+                    );
+                };
+                ensure!(
+                    *action.workspace() == ws_key,
+                    "the last command ran on workspace {}, focus it to undo it",
+                    action.workspace()
+                );
+                match action {
+                    UndoAction::SwapMain { main, other, .. } => {
+                        let mut ctrl =
+                            command_handlers::layout::stack_main::StackMain::new(self.dry_run)
+                                .await?;
+                        ctrl.stack_unswap_main(main, other).await?;
+                    }
+                    UndoAction::SetSize { size, .. } => {
+                        let WorkspaceLayout::StackMain {
+                            stack_layout,
+                            main_position,
+                            ..
+                        } = current_layout
+                        else {
+                            bail!(
+                                "workspace {ws_key} is no longer stack_main, can't undo the resize"
                             );
-                            conn.run_command(cmd).await?;
-                            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                        };
+                        let mut ctrl =
+                            command_handlers::layout::stack_main::StackMain::new(self.dry_run)
+                                .await?;
+                        ctrl.stack_main_set_size(
+                            size,
+                            main_position,
+                            self.stack_main_settings.gap_aware_resize,
+                        )
+                        .await?;
+                        self.set_layout(
+                            ws_key,
+                            WorkspaceLayout::StackMain {
+                                size,
+                                stack_layout,
+                                main_position,
+                            },
+                        );
+                    }
+                    UndoAction::Layout { layout, .. } => {
+                        self.change_layout(ws_key, &current_layout, layout);
+                    }
+                }
+            }
+
+            PerswayCommand::ApplyPreset { name, .. } => {
+                let layout = self
+                    .presets
+                    .get(&name)
+                    .cloned()
+                    .with_context(|| format!("no preset named {name}"))?;
+                self.change_layout(ws_key, &current_layout, layout);
+            }
+
+            PerswayCommand::CycleLayout { layouts, .. } => {
+                let layouts = if layouts.is_empty() {
+                    default_layout_cycle(&self.stack_main_default)
+                } else {
+                    layouts
+                };
+                let layout = next_layout(&layouts, &current_layout);
+                self.change_layout(ws_key, &current_layout, layout);
+            }
+
+            PerswayCommand::Relayout { .. } => {
+                log::debug!("relayout of ws {ws_key} as {current_layout}");
+                self.relayout(ws_key);
+            }
+
+            PerswayCommand::ToggleManual { .. } => {
+                if current_layout == WorkspaceLayout::Manual {
+                    let layout = self
+                        .workspace_config
+                        .get(&ws_key)
+                        .and_then(|config| config.previous_layout.clone())
+                        .context(format!(
+                            "workspace {ws_key} has no layout to restore, use change-layout"
+                        ))?;
+                    self.change_layout(ws_key, &current_layout, layout);
+                } else {
+                    // Freeze the windows where they are
+                    self.set_layout(ws_key, WorkspaceLayout::Manual);
+                }
+            }
+
+            PerswayCommand::FocusBack => self.focus_back().await?,
+
+            PerswayCommand::FocusApp { app_id } => {
+                let id = self.focus_app(&app_id).await?;
+                return Ok(Some(format!("focused window {id}")));
+            }
+
+            PerswayCommand::MergeWorkspace { from, to } => self.merge_workspace(from, to).await?,
+
+            PerswayCommand::SwapWorkspaces { a, b } => {
+                ensure!(a != b, "cannot swap workspace {a} with itself");
+                let workspaces = self.connection.get_workspaces().await?;
+                for key in [&a, &b] {
+                    ensure!(
+                        workspaces.iter().any(|w| WorkspaceKey::of(w) == *key),
+                        "no workspace {key}"
+                    );
+                }
+                if let Err(e) = self.relayout_tx.send(RelayoutJob::Swap {
+                    a: a.clone(),
+                    b: b.clone(),
+                }) {
+                    log::error!("failed to send swap to the relayout handler: {e}");
+                }
+                return Ok(Some(format!("swapping workspaces {a} and {b}")));
+            }
+
+            PerswayCommand::Flatten => {
+                let keep_stack = matches!(current_layout, WorkspaceLayout::StackMain { .. });
+                let mut ctrl = command_handlers::misc::flatten::Flatten::new(self.dry_run).await?;
+                let removed = ctrl.workspace(keep_stack).await?;
+                return Ok(Some(format!("removed {removed} nested containers")));
+            }
+
+            PerswayCommand::FocusedWindow => {
+                let tree = self.connection.get_tree().await?;
+                let wstree = tree
+                    .find_as_ref(|n| n.id == ws.id)
+                    .context("focused workspace not in the tree")?;
+                let focused = wstree
+                    .find_as_ref(|n| n.focused && (n.is_window() || n.is_floating_window()))
+                    .context("no focused window")?;
+                let info = json!({
+                    "id": focused.id,
+                    "app_id": focused.app_id_or_class(),
+                    "title": focused.name,
+                    "role": window_role(wstree, focused, &current_layout),
+                    "workspace": ws.name,
+                });
+                return Ok(Some(info.to_string()));
+            }
+
+            PerswayCommand::ListWindows => {
+                let tree = self.connection.get_tree().await?;
+                let wstree = tree
+                    .find_as_ref(|n| n.id == ws.id)
+                    .context("focused workspace not in the tree")?;
+                let mut lines = vec![format!(
+                    "workspace {ws_key} ('{}') layout={current_layout}",
+                    ws.name
+                )];
+                list_nodes(wstree, wstree, &current_layout, 1, &mut lines);
+                return Ok(Some(lines.join("\n")));
+            }
+
+            PerswayCommand::ListMarks => {
+                let mut ctrl = command_handlers::misc::marks::Marks::new(self.dry_run).await?;
+                return Ok(Some(ctrl.list().await?.join("\n")));
+            }
+
+            PerswayCommand::CleanMarks => {
+                let mut ctrl = command_handlers::misc::marks::Marks::new(self.dry_run).await?;
+                return Ok(Some(ctrl.clean().await?.join("\n")));
+            }
+
+            PerswayCommand::GcWorkspaces => {
+                let live = self
+                    .connection
+                    .get_workspaces()
+                    .await?
+                    .iter()
+                    .map(WorkspaceKey::of)
+                    .collect::<HashSet<_>>();
+                let mut stale = self
+                    .workspace_config
+                    .keys()
+                    .filter(|key| !live.contains(*key))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                stale.sort();
+                for key in &stale {
+                    self.workspace_config.remove(key);
+                }
+                let names = stale.iter().map(ToString::to_string).collect::<Vec<_>>();
+                return Ok(Some(if names.is_empty() {
+                    "no stale workspace configs".to_owned()
+                } else {
+                    format!(
+                        "removed {} workspace configs: {}",
+                        names.len(),
+                        names.join(", ")
+                    )
+                }));
+            }
+
+            PerswayCommand::Pause { all } => {
+                self.enabled = false;
+                self.paused_hooks = all;
+                return Ok(Some(if all {
+                    "paused layouts, renaming and focus hooks".to_owned()
+                } else {
+                    "paused layouts".to_owned()
+                }));
+            }
+
+            PerswayCommand::Resume => {
+                let was_paused = !mem::replace(&mut self.enabled, true);
+                self.paused_hooks = false;
+                return Ok(Some(if was_paused {
+                    "resumed".to_owned()
+                } else {
+                    "not paused".to_owned()
+                }));
+            }
+
+            PerswayCommand::SetFocusHooks { focus, leave } => {
+                let hooks = FocusHooks {
+                    focus: focus.map(|words| words.join(" ")),
+                    leave: leave.map(|words| words.join(" ")),
+                };
+                let reply = if hooks.is_global() {
+                    format!("workspace {ws_key} uses the global focus hooks")
+                } else {
+                    let show = |cmd: &Option<String>| {
+                        match cmd.as_deref() {
+                            None => "global",
+                            Some("") => "none",
+                            Some(cmd) => cmd,
                         }
-                        Ok(())
+                        .to_owned()
+                    };
+                    format!(
+                        "workspace {ws_key} focus hooks: focus: {}, leave: {}",
+                        show(&hooks.focus),
+                        show(&hooks.leave)
+                    )
+                };
+                self.get_workspace_config(ws_key, &ws.name, &ws.output)
+                    .focus_hooks = hooks;
+                return Ok(Some(reply));
+            }
+
+            PerswayCommand::LockName { name } => {
+                let name = format!("{}: {}", workspace_name_prefix(&ws.name), name.join(" "));
+                let cmd = format!("rename workspace to {}", utils::quote_arg(&name));
+                utils::run_command(&mut self.connection, cmd, self.dry_run).await?;
+                self.renamer_config
+                    .locked
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(ws_key.clone());
+                events::publish(
+                    &self.events,
+                    PerswayEvent::WorkspaceRenamed {
+                        workspace: ws_key.clone(),
+                        name: name.clone(),
                     },
-                ));
+                );
+                return Ok(Some(format!("workspace {ws_key} locked as '{name}'")));
+            }
+
+            PerswayCommand::UnlockName => {
+                let unlocked = self
+                    .renamer_config
+                    .locked
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&ws_key);
+                return Ok(Some(if unlocked {
+                    format!("workspace {ws_key} is renamed automatically again")
+                } else {
+                    format!("the name of workspace {ws_key} is not locked")
+                }));
+            }
+
+            PerswayCommand::ToggleRenaming => {
+                self.workspace_renaming = !self.workspace_renaming;
+                if !self.workspace_renaming
+                    && let Some(handle) = self.rename_handle.take()
+                {
+                    handle.abort();
+                }
+                return Ok(Some(format!(
+                    "workspace renaming: {}",
+                    if self.workspace_renaming { "on" } else { "off" }
+                )));
+            }
+
+            PerswayCommand::SaveSnapshot { name } => {
+                let tree = self.connection.get_tree().await?;
+                let node = tree
+                    .find_as_ref(|n| n.id == ws.id)
+                    .context("focused workspace not in the tree")?;
+                let snapshot = Snapshot::capture(node, current_layout);
+                let path = snapshot.save(&name)?;
+                return Ok(Some(format!(
+                    "saved {} windows to {}",
+                    snapshot.windows.len(),
+                    path.display()
+                )));
+            }
+
+            PerswayCommand::RestoreSnapshot { name } => {
+                let snapshot = Snapshot::load(&name)?;
+                snapshot.layout.validate()?;
+                let tree = self.connection.get_tree().await?;
+                let (windows, missing): (Vec<_>, Vec<_>) = snapshot
+                    .windows
+                    .into_iter()
+                    .partition(|w| tree.find_as_ref(|n| n.id == w.id).is_some());
+                let lines = missing
+                    .iter()
+                    .map(|w| {
+                        log::warn!("snapshot {name}: window {} ({}) is gone", w.id, w.app);
+                        format!("skipped window {} ({}), it no longer exists", w.id, w.app)
+                    })
+                    .collect::<Vec<_>>();
+                let resize = snapshot.layout == WorkspaceLayout::Manual;
+                self.set_layout(ws_key.clone(), snapshot.layout);
+                if let Err(e) = self.relayout_tx.send(RelayoutJob::Restore {
+                    key: ws_key,
+                    windows,
+                    resize,
+                }) {
+                    log::error!("failed to send restore to the relayout handler: {e}");
+                }
+                return Ok((!lines.is_empty()).then(|| lines.join("\n")));
+            }
+
+            PerswayCommand::ScratchToggle { mark } => {
+                let mut ctrl = command_handlers::misc::scratch::Scratch::new(self.dry_run).await?;
+                ctrl.toggle(&mark).await?;
+            }
+
+            PerswayCommand::PinToggle => {
+                let mut ctrl = command_handlers::misc::pin::Pin::new(self.dry_run).await?;
+                let (id, pinned) = ctrl.toggle(&mut self.pinned).await?;
+                let state = if pinned { "pinned" } else { "unpinned" };
+                return Ok(Some(format!("{state} window {id}")));
+            }
+
+            PerswayCommand::Stash => {
+                let mut ctrl = command_handlers::misc::stash::Stash::new(self.dry_run).await?;
+                let (id, number) = ctrl.stash().await?;
+                self.stash.push(id);
+                return Ok(Some(format!("stashed window {id} on workspace {number}")));
+            }
+
+            PerswayCommand::Unstash => {
+                let mut ctrl = command_handlers::misc::stash::Stash::new(self.dry_run).await?;
+                let Some(id) = ctrl.unstash(&mut self.stash).await? else {
+                    bail!("no stashed window left, they were all closed or unstashed");
+                };
+                return Ok(Some(format!("unstashed window {id}")));
+            }
+
+            PerswayCommand::SwapMarks { a, b } => {
+                let mut ctrl = command_handlers::misc::swap::Swap::new(self.dry_run).await?;
+                ctrl.marks(&a, &b).await?;
+            }
+
+            PerswayCommand::Transpose => {
+                ensure!(
+                    matches!(
+                        current_layout,
+                        WorkspaceLayout::Manual | WorkspaceLayout::Spiral
+                    ),
+                    CommandError::WrongLayout {
+                        cmd: "transpose",
+                        workspace: format!("{ws_key} ('{}')", ws.name),
+                        layout: current_layout.clone(),
+                        required: "manual or spiral",
+                    }
+                );
+                let mut ctrl = command_handlers::layout::manual::Manual::new(self.dry_run).await?;
+                ctrl.transpose().await?;
+            }
+
+            PerswayCommand::SpiralForceDir { dir } => {
+                ensure!(
+                    current_layout == WorkspaceLayout::Spiral,
+                    CommandError::WrongLayout {
+                        cmd: "spiral-force-dir",
+                        workspace: format!("{ws_key} ('{}')", ws.name),
+                        layout: current_layout.clone(),
+                        required: "spiral",
+                    }
+                );
+                self.get_workspace_config(ws_key, &ws.name, &ws.output)
+                    .spiral_dir = dir;
+                return Ok(Some(format!("spiral split direction: {dir}")));
+            }
+
+            PerswayCommand::FocusSpatial { dir } => {
+                let mut ctrl =
+                    command_handlers::layout::spatial::Spatial::new(self.dry_run).await?;
+                ctrl.focus(dir).await?;
+            }
+
+            PerswayCommand::SwapDirection { dir } => {
+                let mut ctrl = command_handlers::layout::manual::Manual::new(self.dry_run).await?;
+                ctrl.swap_direction(dir).await?;
             }
 
             PerswayCommand::StackFocusNext => {
-                Self::require_stack_main(ws.num, &ws.name, &current_layout, "stack-focus-next")?;
-                let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-focus-next")?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
                 ctrl.stack_focus_next().await?;
             }
 
             PerswayCommand::StackFocusPrev => {
-                Self::require_stack_main(ws.num, &ws.name, &current_layout, "stack-focus-prev")?;
-                let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-focus-prev")?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
                 ctrl.stack_focus_prev().await?;
             }
 
+            PerswayCommand::StackPageNext => {
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-page-next")?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                ctrl.stack_page_next().await?;
+            }
+
+            PerswayCommand::StackPagePrev => {
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-page-prev")?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                ctrl.stack_page_prev().await?;
+            }
+
             PerswayCommand::StackMainRotatePrev => {
                 Self::require_stack_main(
-                    ws.num,
+                    &ws_key,
                     &ws.name,
                     &current_layout,
                     "stack-main-rotate-prev",
                 )?;
-                let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
                 ctrl.stack_main_rotate_prev().await?;
             }
 
             PerswayCommand::StackMainRotateNext => {
                 Self::require_stack_main(
-                    ws.num,
+                    &ws_key,
                     &ws.name,
                     &current_layout,
                     "stack-main-rotate-next",
                 )?;
-                let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
                 ctrl.stack_main_rotate_next().await?;
             }
 
             PerswayCommand::StackSwapMain => {
-                Self::require_stack_main(ws.num, &ws.name, &current_layout, "stack-swap-main")?;
-                let mut ctrl = command_handlers::layout::stack_main::StackMain::new().await?;
-                ctrl.stack_swap_main().await?;
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-swap-main")?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                if let Some((main, other)) = ctrl.stack_swap_main().await? {
+                    self.undo = Some(UndoAction::SwapMain {
+                        workspace: ws_key,
+                        main,
+                        other,
+                    });
+                }
+            }
+
+            PerswayCommand::StackMove { dir } => {
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-move")?;
+                let WorkspaceLayout::StackMain { main_position, .. } = current_layout else {
+                    unreachable!()
+                };
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                ctrl.stack_move(dir, main_position).await?;
+            }
+
+            PerswayCommand::StackSwapLastFocused => {
+                Self::require_stack_main(
+                    &ws_key,
+                    &ws.name,
+                    &current_layout,
+                    "stack-swap-last-focused",
+                )?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                if let Some((main, other)) =
+                    ctrl.stack_swap_last_focused(&self.focus_history).await?
+                {
+                    self.undo = Some(UndoAction::SwapMain {
+                        workspace: ws_key,
+                        main,
+                        other,
+                    });
+                }
+            }
+
+            PerswayCommand::StackMainSetSize { size } => {
+                Self::require_stack_main(
+                    &ws_key,
+                    &ws.name,
+                    &current_layout,
+                    "stack-main-set-size",
+                )?;
+                let WorkspaceLayout::StackMain {
+                    size: previous_size,
+                    stack_layout,
+                    main_position,
+                } = current_layout
+                else {
+                    unreachable!()
+                };
+                let layout = WorkspaceLayout::StackMain {
+                    size,
+                    stack_layout,
+                    main_position,
+                };
+                layout.validate()?;
+
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                ctrl.stack_main_set_size(
+                    size,
+                    main_position,
+                    self.stack_main_settings.gap_aware_resize,
+                )
+                .await?;
+                self.set_layout(ws_key.clone(), layout);
+                self.undo = Some(UndoAction::SetSize {
+                    workspace: ws_key,
+                    size: previous_size,
+                });
+            }
+
+            PerswayCommand::StackZoomToggle => {
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-zoom-toggle")?;
+                let WorkspaceLayout::StackMain {
+                    size,
+                    stack_layout,
+                    main_position,
+                } = current_layout
+                else {
+                    unreachable!()
+                };
+                let zoomed_from = self
+                    .workspace_config
+                    .get(&ws_key)
+                    .and_then(|config| config.zoomed_from);
+
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                let new_size = if let Some(prior) = zoomed_from {
+                    ctrl.stack_main_set_size(
+                        prior,
+                        main_position,
+                        self.stack_main_settings.gap_aware_resize,
+                    )
+                    .await?;
+                    prior
+                } else {
+                    ctrl.stack_zoom(
+                        STACK_ZOOM_SIZE,
+                        main_position,
+                        self.stack_main_settings.gap_aware_resize,
+                    )
+                    .await?;
+                    STACK_ZOOM_SIZE
+                };
+                let layout = WorkspaceLayout::StackMain {
+                    size: new_size,
+                    stack_layout,
+                    main_position,
+                };
+                self.set_layout(ws_key.clone(), layout);
+                if zoomed_from.is_none() {
+                    self.get_workspace_config(ws_key, &ws.name, &ws.output)
+                        .zoomed_from = Some(size);
+                }
+            }
+
+            PerswayCommand::StackBalance => {
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-balance")?;
+                let mut ctrl =
+                    command_handlers::layout::stack_main::StackMain::new(self.dry_run).await?;
+                ctrl.stack_balance(self.stack_main_settings.gap_aware_resize)
+                    .await?;
             }
 
-            PerswayCommand::Daemon(_) => unreachable!(),
+            PerswayCommand::StackGrow { amount } => {
+                self.stack_resize(&ws, &current_layout, false, amount, "stack-grow")
+                    .await?;
+            }
+
+            PerswayCommand::StackShrink { amount } => {
+                self.stack_resize(&ws, &current_layout, true, amount, "stack-shrink")
+                    .await?;
+            }
+
+            PerswayCommand::StackLayoutToggle => {
+                Self::require_stack_main(
+                    &ws_key,
+                    &ws.name,
+                    &current_layout,
+                    "stack-layout-toggle",
+                )?;
+                let WorkspaceLayout::StackMain {
+                    size,
+                    stack_layout,
+                    main_position,
+                } = current_layout
+                else {
+                    unreachable!()
+                };
+                let stack_layout = match stack_layout {
+                    StackLayout::Tabbed => StackLayout::Stacked,
+                    StackLayout::Stacked | StackLayout::Tiled => StackLayout::Tabbed,
+                };
+                self.set_stack_layout(&ws, size, stack_layout, main_position)
+                    .await?;
+            }
+
+            PerswayCommand::StackSetLayout { stack_layout } => {
+                Self::require_stack_main(&ws_key, &ws.name, &current_layout, "stack-set-layout")?;
+                let WorkspaceLayout::StackMain {
+                    size,
+                    main_position,
+                    ..
+                } = current_layout
+                else {
+                    unreachable!()
+                };
+                self.set_stack_layout(&ws, size, stack_layout, main_position)
+                    .await?;
+            }
+
+            PerswayCommand::Daemon(_)
+            | PerswayCommand::Status
+            | PerswayCommand::DumpConfig
+            | PerswayCommand::Ping
+            | PerswayCommand::SetLogLevel { .. }
+            | PerswayCommand::Quit => {
+                unreachable!()
+            }
 
             #[cfg(feature = "wallpaper")]
             PerswayCommand::SetWallpaper { .. } => unreachable!(),
         }
 
-        Ok(())
+        Ok(None)
     }
 }
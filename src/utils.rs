@@ -1,23 +1,108 @@
 use crate::node_ext::NodeExt;
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::thread::sleep;
-use std::{fmt::Write, future::Future, time::Duration};
+use std::{fmt, fmt::Write, future::Future, io, time::Duration};
 use swayipc_async::{Connection, Node, Workspace};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream};
 
 pub const PERSWAY_TMP_WORKSPACE: &str = "◕‿◕";
 pub const SCRATCHPAD_WORKSPACE: &str = "__i3_scratch";
 
+/// How persway tells workspaces apart for per-workspace settings.
+///
+/// Numbered workspaces are keyed by number. Workspaces without one (eg. `www`)
+/// are keyed by the part of their name before the first `:`, which is also the
+/// part the workspace renamer keeps, so the key survives renames.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WorkspaceKey {
+    Num(i32),
+    Name(String),
+}
+
+impl WorkspaceKey {
+    pub fn new(num: i32, name: &str) -> Self {
+        if num >= 0 {
+            Self::Num(num)
+        } else {
+            Self::Name(name.split(':').next().unwrap_or(name).trim().to_owned())
+        }
+    }
+
+    pub fn of(ws: &Workspace) -> Self {
+        Self::new(ws.num, &ws.name)
+    }
+
+    /// Key of the workspace `node` from the tree.
+    pub fn of_node(node: &Node) -> Self {
+        Self::new(node.num.unwrap_or(-1), node.name.as_deref().unwrap_or(""))
+    }
+
+    /// Whether the workspace `node` from the tree is the workspace with this key.
+    pub fn matches(&self, node: &Node) -> bool {
+        node.is_workspace() && *self == Self::of_node(node)
+    }
+}
+
+/// Parse a workspace given on the command line: a number, or a name.
+impl FromStr for WorkspaceKey {
+    type Err = Infallible;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s.parse().unwrap_or(-1), s))
+    }
+}
+
+impl fmt::Display for WorkspaceKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Num(num) => write!(f, "{num}"),
+            Self::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// `arg` as a double-quoted sway command argument, so `;`, `,` and quotes in it
+/// can't end the command or start another one.
+pub fn quote_arg(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The argument to `workspace`/`move to workspace` that refers to an existing workspace.
+pub fn workspace_target(num: i32, name: &str) -> String {
+    if num >= 0 {
+        format!("number {num}")
+    } else {
+        format!("\"{name}\"")
+    }
+}
+
 pub async fn get_focused_workspace(conn: &mut Connection) -> Result<Workspace> {
     let mut ws = conn.get_workspaces().await?.into_iter();
     ws.find(|w| w.focused).context("no focused workspace")
 }
 
-pub fn get_socket_path(socket_path: Option<String>) -> String {
+/// Check a `--profile` name, which becomes part of the socket file name.
+pub fn parse_profile(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.contains('/') || s.starts_with('.') {
+        return Err(format!("invalid profile name '{s}'"));
+    }
+    Ok(s.to_owned())
+}
+
+/// Path of the control socket, `<XDG_RUNTIME_DIR>/persway-<WAYLAND_DISPLAY>.sock`
+/// unless `socket_path` is given. With a `profile` it is
+/// `persway-<WAYLAND_DISPLAY>-<profile>.sock`, so several daemons can run side by side.
+pub fn get_socket_path(socket_path: Option<String>, profile: Option<&str>) -> String {
     let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR");
     let wayland_display = std::env::var("WAYLAND_DISPLAY");
+    let suffix = profile.map(|p| format!("-{p}")).unwrap_or_default();
     socket_path.unwrap_or_else(|| {
         format!(
-            "{}/persway-{}.sock",
+            "{}/persway-{}{suffix}.sock",
             match xdg_runtime_dir {
                 Ok(dir) => dir,
                 Err(_e) => {
@@ -36,6 +121,135 @@ pub fn get_socket_path(socket_path: Option<String>) -> String {
     })
 }
 
+/// Path of the control socket a client should connect to, see `get_socket_path`.
+///
+/// Without `WAYLAND_DISPLAY` (eg. in scripts or systemd units) the client looks for
+/// `persway-*.sock` in `XDG_RUNTIME_DIR` and uses it if there is exactly one. With
+/// several of them it fails, listing them so one can be passed with `--socket-path`.
+/// With a `profile` only the sockets of that profile are looked at.
+pub fn discover_socket_path(profile: Option<&str>) -> Result<String> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Ok(get_socket_path(None, profile));
+    }
+    let dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| String::from("/tmp"));
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok(get_socket_path(None, profile));
+    };
+    let suffix = profile.map_or_else(|| String::from(".sock"), |p| format!("-{p}.sock"));
+    let mut sockets = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| {
+            name.starts_with("persway-")
+                && name.ends_with(&suffix)
+                && !name.ends_with(".events.sock")
+        })
+        .map(|name| format!("{dir}/{name}"))
+        .collect::<Vec<_>>();
+    sockets.sort();
+    match sockets.len() {
+        0 => Ok(get_socket_path(None, profile)),
+        1 => {
+            log::debug!("WAYLAND_DISPLAY not set, using {}", sockets[0]);
+            Ok(sockets.remove(0))
+        }
+        _ => bail!(
+            "WAYLAND_DISPLAY is not set and there are several persway sockets, \
+             pick one with --socket-path:\n{}",
+            sockets.join("\n")
+        ),
+    }
+}
+
+/// Path of the events socket that lives next to the control socket at `socket_path`.
+///
+/// `persway-wayland-1.sock` becomes `persway-wayland-1.events.sock`.
+pub fn get_events_socket_path(socket_path: &str) -> String {
+    socket_path.strip_suffix(".sock").map_or_else(
+        || format!("{socket_path}.events"),
+        |base| format!("{base}.events.sock"),
+    )
+}
+
+/// Where the control socket listens, see `--listen`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    /// Unix socket file, `unix:/path` or just `/path`.
+    Unix(String),
+    /// Linux abstract Unix socket, `abstract:@name`. Held without the `@`.
+    Abstract(String),
+    /// TCP socket, `tcp:127.0.0.1:PORT`.
+    Tcp(SocketAddr),
+}
+
+impl FromStr for ListenAddr {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            Ok(Self::Unix(path.to_owned()))
+        } else if let Some(name) = s.strip_prefix("abstract:") {
+            let name = name.strip_prefix('@').unwrap_or(name);
+            if name.is_empty() {
+                bail!("abstract socket name missing in '{s}'");
+            }
+            Ok(Self::Abstract(name.to_owned()))
+        } else if let Some(addr) = s.strip_prefix("tcp:") {
+            let addr = addr
+                .parse()
+                .map_err(|e| anyhow!("invalid TCP address '{addr}': {e}"))?;
+            Ok(Self::Tcp(addr))
+        } else {
+            Ok(Self::Unix(s.to_owned()))
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unix(path) => write!(f, "{path}"),
+            Self::Abstract(name) => write!(f, "abstract:@{name}"),
+            Self::Tcp(addr) => write!(f, "tcp:{addr}"),
+        }
+    }
+}
+
+/// A connection to the control socket, whatever kind of socket it is.
+pub trait ControlStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ControlStream for T {}
+
+/// Address of the abstract Unix socket `name`.
+#[cfg(target_os = "linux")]
+pub fn abstract_socket_addr(name: &str) -> io::Result<std::os::unix::net::SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())
+}
+
+/// Abstract Unix sockets only exist on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn abstract_socket_addr(_name: &str) -> io::Result<std::os::unix::net::SocketAddr> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract sockets are only supported on Linux",
+    ))
+}
+
+impl ListenAddr {
+    /// Connect to the control socket listening on this address.
+    pub async fn connect(&self) -> io::Result<Box<dyn ControlStream>> {
+        Ok(match self {
+            Self::Unix(path) => Box::new(UnixStream::connect(path).await?),
+            Self::Abstract(name) => {
+                let stream =
+                    std::os::unix::net::UnixStream::connect_addr(&abstract_socket_addr(name)?)?;
+                stream.set_nonblocking(true)?;
+                Box::new(UnixStream::from_std(stream)?)
+            }
+            Self::Tcp(addr) => Box::new(TcpStream::connect(addr).await?),
+        })
+    }
+}
+
 pub fn is_scratchpad_workspace(ws: &Workspace) -> bool {
     ws.name == SCRATCHPAD_WORKSPACE
 }
@@ -44,26 +258,60 @@ pub fn is_persway_tmp_workspace(ws: &Workspace) -> bool {
     ws.name == PERSWAY_TMP_WORKSPACE
 }
 
-pub async fn relayout_workspace<F, C>(ws_num: i32, f: C) -> Result<()>
+/// Whether a workspace node from the tree is one of the "special" workspaces
+/// (persway's temporary workspace or the scratchpad) that layouts leave alone.
+pub fn is_special_workspace_node(ws: &Node) -> bool {
+    matches!(
+        ws.name.as_deref(),
+        Some(PERSWAY_TMP_WORKSPACE | SCRATCHPAD_WORKSPACE)
+    )
+}
+
+/// The tree of the current event pass, fetching it if the pass didn't.
+pub async fn tree_or_fetch(conn: &mut Connection, tree: Option<Arc<Node>>) -> Result<Arc<Node>> {
+    match tree {
+        Some(tree) => Ok(tree),
+        None => Ok(Arc::new(conn.get_tree().await?)),
+    }
+}
+
+/// Run `cmd` on `connection`, or only log it at info level when `dry_run` is set.
+pub async fn run_command(connection: &mut Connection, cmd: String, dry_run: bool) -> Result<()> {
+    if dry_run {
+        log::info!("dry run: {cmd}");
+        return Ok(());
+    }
+    connection.run_command(cmd).await?;
+    Ok(())
+}
+
+/// Move workspace `key` out of the way and let `f` lay its windows out again.
+///
+/// `f` gets a connection, the `workspace_target` to move windows back to, the
+/// workspace and output ids and the workspace's windows.
+///
+/// The workspace focused before, which need not be `key` nor on its output, is
+/// focused again afterwards and stays on the output it was on.
+pub async fn relayout_workspace<F, C>(key: WorkspaceKey, dry_run: bool, f: C) -> Result<()>
 where
     F: Future<Output = Result<()>>,
-    C: FnOnce(Connection, i32, i64, i64, Vec<Node>) -> F,
+    C: FnOnce(Connection, String, i64, i64, Vec<Node>) -> F,
 {
     let mut connection = Connection::new().await?;
     let tree = connection.get_tree().await?;
     let workspaces = connection.get_workspaces().await?;
     let output = tree
         .iter()
-        .find(|n| {
-            n.is_output()
-                && n.iter()
-                    .any(|n| n.is_workspace() && n.num.unwrap() == ws_num)
-        })
+        .find(|n| n.is_output() && n.iter().any(|n| key.matches(n)))
         .context("no output found")?;
     let ws = output
         .iter()
-        .find(|n| n.is_workspace() && n.num.unwrap() == ws_num)
+        .find(|n| key.matches(n))
         .context("no workspace found")?;
+    let ws_target = workspace_target(
+        ws.num.unwrap_or(-1),
+        ws.name.as_deref().context("workspace has no name")?,
+    );
     let focused_workspace = workspaces
         .iter()
         .find(|w| w.focused)
@@ -80,10 +328,10 @@ where
     )
     .expect("Failed to write string");
     log::debug!("relayout before layout closure: {cmd}");
-    connection.run_command(cmd).await?;
+    run_command(&mut connection, cmd, dry_run).await?;
     sleep(Duration::from_millis(50));
     let closure_conn = Connection::new().await?;
-    f(closure_conn, ws_num, ws.id, output.id, windows).await?;
+    f(closure_conn, ws_target, ws.id, output.id, windows).await?;
     sleep(Duration::from_millis(50));
     let workspaces = connection.get_workspaces().await?;
     let focused_workspace_after_closure = workspaces
@@ -91,12 +339,25 @@ where
         .find(|w| w.focused)
         .context("no focused workspace")?;
     let mut cmd = String::new();
-    if focused_workspace_after_closure.num != focused_workspace.num {
+    // By name, the workspace may have been recreated through the tmp workspace
+    if focused_workspace_after_closure.name != focused_workspace.name {
         write!(
             cmd,
-            "workspace number {focused_workspace_num}; move workspace to output {output_id}; ",
-            focused_workspace_num = focused_workspace.num,
-            output_id = output.id,
+            "workspace {}; ",
+            workspace_target(focused_workspace.num, &focused_workspace.name)
+        )
+        .unwrap();
+    }
+    // Gone workspaces are recreated on the output that has focus, which may not be theirs
+    let moved = workspaces
+        .iter()
+        .find(|w| w.name == focused_workspace.name)
+        .is_none_or(|w| w.output != focused_workspace.output);
+    if moved {
+        write!(
+            cmd,
+            "move workspace to output \"{}\"; ",
+            focused_workspace.output
         )
         .unwrap();
     }
@@ -107,6 +368,6 @@ where
     )
     .unwrap();
     log::debug!("rename new workspace to old name after layout closure: {cmd}");
-    connection.run_command(cmd).await?;
+    run_command(&mut connection, cmd, dry_run).await?;
     Ok(())
 }